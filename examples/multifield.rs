@@ -1,6 +1,6 @@
 use std::env;
 
-use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use chrono::{DateTime, FixedOffset};
 use influxdb2::models::DataPoint;
 use influxdb2::models::Query;
 use influxdb2::{Client, FromDataPoint};
@@ -10,21 +10,10 @@ pub struct StockPrice {
     ticker: String,
     value: f64,
     open: f64,
+    #[influxdb(rename = "_time")]
     time: DateTime<FixedOffset>,
 }
 
-impl Default for StockPrice {
-    fn default() -> Self {
-        let now = Utc::now().naive_utc();
-        Self {
-            ticker: "".to_owned(),
-            value: 0.0,
-            open: 0.0,
-            time: FixedOffset::east(7 * 3600).from_utc_datetime(&now),
-        }
-    }
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let host = env::var("INFLUXDB_HOST").unwrap();
     let org = env::var("INFLUXDB_ORG").unwrap();
@@ -57,10 +46,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
     let query = Query::new(qs.to_string());
 
-    println!(
-        "Query result was: {:#?}",
-        client.query::<StockPrice>(Some(query))?
-    );
+    for price in client.query::<StockPrice>(Some(query))? {
+        println!(
+            "{} {}: value={} open={}",
+            price.time, price.ticker, price.value, price.open
+        );
+    }
 
     Ok(())
 }
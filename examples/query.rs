@@ -12,17 +12,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     #[derive(FromDataPoint)]
     struct Measurement {
+        #[influxdb(rename = "_value")]
         value: f64,
     }
-    impl Default for Measurement {
-        fn default() -> Self {
-            Self { value: 0f64 }
-        }
-    }
 
-    client
-        .query::<Measurement>(Some(Query::new("some-query".to_string())))
-        ?;
+    for measurement in client.query::<Measurement>(Some(Query::new("some-query".to_string())))? {
+        println!("{}", measurement.value);
+    }
 
     client
         .query_analyze(Some(Query::new("some-query".to_string())))
@@ -27,13 +27,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             host: Some("server01".to_owned()),
             region: Some("us-west".to_owned()),
             value: 0.64,
-            time: Utc::now().timestamp_nanos(),
+            time: Utc::now().timestamp_nanos_opt().unwrap(),
         },
         CpuLoadShort {
             host: Some("server02".to_owned()),
             region: None,
             value: 0.64,
-            time: Utc::now().timestamp_nanos(),
+            time: Utc::now().timestamp_nanos_opt().unwrap(),
         },
     ];
 
@@ -0,0 +1,215 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Field, GenericArgument, Lit, Meta, MetaNameValue,
+    NestedMeta, PathArguments, Type,
+};
+
+const INFLUX_TAG: &str = "influxdb";
+
+const SUPPORTED_TYPES: &str = "f64, i64, u64, bool, String, Vec<u8>, \
+    chrono::Duration and chrono::DateTime, optionally in an Option";
+
+pub fn impl_from_data_point(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    let fields = match ast.data {
+        Data::Struct(st) => st.fields,
+        _ => panic!("Implementation must be a struct"),
+    };
+
+    let mut idents = Vec::new();
+    let mut assignments = Vec::new();
+    for field in fields.iter() {
+        let ident = field.ident.as_ref().expect("a struct with named fields");
+        let ty = &field.ty;
+        let attrs = FieldAttrs::from(field);
+        idents.push(ident);
+
+        if attrs.skip {
+            assignments.push(quote! {
+                let #ident: #ty = ::std::default::Default::default();
+            });
+            continue;
+        }
+
+        // `Option` fields are `None` when the column is missing
+        let inner = option_inner(ty);
+        let value_ty = inner.unwrap_or(ty);
+        let (variant, convert) = match value_variant(value_ty) {
+            Some(v) => v,
+            None => {
+                let message = format!(
+                    "{} is not handled, the supported types are {}",
+                    type_name(value_ty),
+                    SUPPORTED_TYPES
+                );
+                return syn::Error::new_spanned(field, message)
+                    .to_compile_error()
+                    .into();
+            }
+        };
+        let expected = type_name(value_ty);
+        let key = attrs.rename.unwrap_or_else(|| ident.unraw().to_string());
+
+        let found = if inner.is_some() {
+            quote!(Some(::std::convert::Into::into(#convert)))
+        } else {
+            quote!(::std::convert::Into::into(#convert))
+        };
+        let missing = if inner.is_some() {
+            quote!(None)
+        } else if attrs.default {
+            quote!(::std::default::Default::default())
+        } else {
+            quote! {
+                return Err(influxdb2_structmap::FromMapError::MissingKey {
+                    key: #key.to_string(),
+                })
+            }
+        };
+
+        assignments.push(quote! {
+            let #ident: #ty = match hashmap.remove(#key) {
+                None | Some(influxdb2_structmap::value::Value::Unknown) => #missing,
+                Some(influxdb2_structmap::value::Value::#variant(v)) => #found,
+                Some(found) => {
+                    return Err(influxdb2_structmap::FromMapError::TypeMismatch {
+                        key: #key.to_string(),
+                        expected: #expected,
+                        found,
+                    })
+                }
+            };
+        });
+    }
+
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let tokens = quote! {
+        impl #impl_generics influxdb2_structmap::FromMap for #name #ty_generics #where_clause {
+
+            fn from_genericmap(
+                mut hashmap: influxdb2_structmap::GenericMap,
+            ) -> Result<Self, influxdb2_structmap::FromMapError> {
+                #(
+                    #assignments
+                )*
+
+                Ok(Self {
+                    #(#idents),*
+                })
+            }
+
+        }
+    };
+    TokenStream::from(tokens)
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    default: bool,
+    skip: bool,
+}
+
+impl FieldAttrs {
+    // Options only used by `WriteDataPoint` are ignored.
+    fn from(field: &Field) -> Self {
+        let mut attrs = Self::default();
+        let nested = field
+            .attrs
+            .iter()
+            .filter(|a| a.path.is_ident(INFLUX_TAG))
+            .filter_map(|a| match a.parse_meta() {
+                Ok(Meta::List(list)) => Some(list.nested),
+                _ => None,
+            })
+            .flatten();
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::Path(p)) => {
+                    if p.is_ident("default") {
+                        attrs.default = true;
+                    } else if p.is_ident("skip") || p.is_ident("ignore") {
+                        attrs.skip = true;
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("rename") => attrs.rename = Some(lit.value()),
+                _ => {}
+            }
+        }
+        attrs
+    }
+}
+
+// The last segment of a type path, e.g. `String` for `std::string::String`
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+fn first_type_argument(segment: &syn::PathSegment) -> Option<&Type> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+// `T` for an `Option<T>`
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let segment = last_segment(ty)?;
+    if segment.ident == "Option" {
+        first_type_argument(segment)
+    } else {
+        None
+    }
+}
+
+// The `Value` variant holding a type and how to get the type out of it.
+//
+// Types are matched by path, as written in the struct: a plain `Duration` or
+// `DateTime` is taken to be imported from chrono, other paths have to be the
+// full chrono ones so that e.g. `std::time::Duration` is refused.
+fn value_variant(ty: &Type) -> Option<(TokenStream2, TokenStream2)> {
+    let path = match ty {
+        Type::Path(path) if path.qself.is_none() => &path.path,
+        _ => return None,
+    };
+    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    let segments: Vec<_> = segments.iter().map(String::as_str).collect();
+    let res = match segments.as_slice() {
+        ["f64"] => (quote!(Double), quote!(v.into_inner())),
+        ["i64"] => (quote!(Long), quote!(v)),
+        ["u64"] => (quote!(UnsignedLong), quote!(v)),
+        ["bool"] => (quote!(Bool), quote!(v)),
+        ["String"] | ["std" | "alloc", "string", "String"] => (quote!(String), quote!(v)),
+        ["Duration"] | ["chrono", "Duration" | "TimeDelta"] => (quote!(Duration), quote!(v)),
+        ["DateTime"] | ["chrono", "DateTime"] => (quote!(TimeRFC), quote!(v)),
+        ["Vec"] | ["std" | "alloc", "vec", "Vec"] => {
+            let segment = path.segments.last()?;
+            match first_type_argument(segment).and_then(last_segment) {
+                Some(arg) if arg.ident == "u8" => (quote!(Base64Binary), quote!(v)),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn type_name(ty: &Type) -> String {
+    ty.to_token_stream().to_string().replace(' ', "")
+}
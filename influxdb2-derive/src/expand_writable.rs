@@ -1,7 +1,11 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{format_ident, quote};
-use syn::{parse_macro_input, Field, Fields, Ident, ItemStruct, Lit, Meta, MetaNameValue, Type};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{
+    parse_macro_input, Field, Fields, Ident, ItemStruct, Lit, Meta, MetaNameValue, NestedMeta,
+    Path, Type,
+};
 
 const INFLUX_TAG: &str = "influxdb";
 
@@ -10,7 +14,7 @@ pub fn impl_writeable(tokens: TokenStream) -> TokenStream {
     let writable_krate = writable_krate();
     let input = parse_macro_input!(tokens as ItemStruct); // only struct is supported now.
     let ident = input.ident;
-    let generics = input.generics;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
     let measurement = input.attrs.into_iter().find_map(|a| {
         let is_outer = match a.style {
             syn::AttrStyle::Outer => true,
@@ -32,7 +36,7 @@ pub fn impl_writeable(tokens: TokenStream) -> TokenStream {
     });
 
     let measure = match measure_value {
-        Some(v) => format_ident!("{}", v).to_string(),
+        Some(v) => v,
         None => ident.clone().to_string(),
     };
 
@@ -40,7 +44,7 @@ pub fn impl_writeable(tokens: TokenStream) -> TokenStream {
         Fields::Named(fields) => fields
             .named
             .into_iter()
-            .filter_map(FieldWritable::from)
+            .map(FieldWritable::from)
             .filter(|field| match field.field_type {
                 FieldType::Ignore => false,
                 _ => true,
@@ -48,17 +52,24 @@ pub fn impl_writeable(tokens: TokenStream) -> TokenStream {
             .collect(),
         _ => panic!("a struct without named fields is not supported"),
     };
+
+    // Tags and fields whose value is `None` are left out of the point.
     let tag_writes: Vec<TokenStream2> = fields
         .iter()
         .filter_map(|f| match f.field_type {
             FieldType::Tag => {
-                let ident = f.ident.clone();
-                let ident_str = ident.to_string();
-                let kind = f.kind.clone();
+                let key = &f.key;
+                let value = f.value();
                 Some(quote! {
-                    w.write_all(format!("{}", #ident_str).as_bytes())?;
-                    w.write_all(b"=")?;
-                    w.write_all(<#kind as #writable_krate::KeyWritable>::encode_key(&self.#ident).into_bytes().as_slice())?;
+                    if let Some(value) = #writable_krate::KeyWritable::encode_optional_key(&(#value)) {
+                        tags.push((#writable_krate::KeyWritable::encode_key(&#key), value));
+                    }
+                })
+            }
+            FieldType::Flatten => {
+                let ident = &f.ident;
+                Some(quote! {
+                    #writable_krate::FlattenWritable::encode_tags_into(&self.#ident, tags)?;
                 })
             }
             _ => None,
@@ -69,83 +80,146 @@ pub fn impl_writeable(tokens: TokenStream) -> TokenStream {
         .iter()
         .filter_map(|f| match f.field_type {
             FieldType::Field => {
-                let ident = f.ident.clone();
-                let ident_str = ident.to_string();
-                let kind = f.kind.clone();
+                let key = &f.key;
+                let value = f.value();
+                Some(quote! {
+                    if let Some(value) = #writable_krate::ValueWritable::encode_optional_value(&(#value)) {
+                        fields.push((#writable_krate::KeyWritable::encode_key(&#key), value));
+                    }
+                })
+            }
+            FieldType::Flatten => {
+                let ident = &f.ident;
                 Some(quote! {
-                    w.write_all(format!("{}", #ident_str).as_bytes())?;
-                    w.write_all(b"=")?;
-                    w.write_all(<#kind as #writable_krate::ValueWritable>::encode_value(&self.#ident).into_bytes().as_slice())?;
+                    #writable_krate::FlattenWritable::encode_fields_into(&self.#ident, fields)?;
                 })
             }
             _ => None,
         })
         .collect();
 
-    let timestamp_writes: Vec<TokenStream2>  = fields
+    let timestamp_writes: Vec<TokenStream2> = fields
         .iter()
         .filter_map(|f| match f.field_type {
             FieldType::Timestamp => {
-                let ident = f.ident.clone();
-                let kind = f.kind.clone();
+                let ident = &f.ident;
+                let kind = &f.kind;
+                let encoded = match &f.precision {
+                    Some(precision) => quote! {
+                        <#kind as #writable_krate::TimestampWritable>::encode_timestamp_with_precision(
+                            &self.#ident,
+                            #krate::api::write::TimestampPrecision::#precision,
+                        )
+                    },
+                    None => quote! {
+                        <#kind as #writable_krate::TimestampWritable>::encode_timestamp(&self.#ident)
+                    },
+                };
                 Some(quote! {
-                    w.write_all(<#kind as #writable_krate::TimestampWritable>::encode_timestamp(&self.#ident).into_bytes().as_slice())?;
+                    w.write_all(b" ")?;
+                    w.write_all(#encoded.as_bytes())?;
                 })
             }
             _ => None,
         })
         .collect();
 
-    if tag_writes.len() < 1 {
-        panic!("You have to specify at least one #[tag] field.")
-    }
-    if timestamp_writes.len() != 1 {
-        panic!("You have to specify at exact one #[timestamp] field.")
+    let measurement_writes: Vec<TokenStream2> = fields
+        .iter()
+        .filter_map(|f| match f.field_type {
+            FieldType::Measurement => {
+                let ident = &f.ident;
+                Some(quote! {
+                    w.write_all(#writable_krate::encode_measurement(&self.#ident.to_string()).as_bytes())?;
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    if timestamp_writes.len() > 1 {
+        panic!("You can specify at most one #[timestamp] field.")
     }
-    if fields_writes.len() < 1 {
-        panic!("You have to specify at least one #[field] field.")
+    // A struct without fields, e.g. a block of common tags, can only be
+    // flattened into another one.
+    let is_point = !fields_writes.is_empty();
+    if !is_point && !(timestamp_writes.is_empty() && measurement_writes.is_empty()) {
+        panic!("A struct with a #[measurement] or #[timestamp] field needs at least one #[field] or #[flatten] field.")
     }
+    let measurement_write = match measurement_writes.len() {
+        0 => quote! {
+            w.write_all(#writable_krate::encode_measurement(#measure).as_bytes())?;
+        },
+        1 => measurement_writes[0].clone(),
+        _ => panic!("You can specify at most one #[measurement] field."),
+    };
 
-    let mut combined_tag_writes = vec![];
-    for (index, tag_write) in tag_writes.iter().enumerate() {
-        if index > 0 {
-            combined_tag_writes.push(quote!(w.write_all(b",")?;));
-        }
-        combined_tag_writes.push(tag_write.clone());
-    }
+    let write_data_point = if is_point {
+        quote! {
+            impl #impl_generics #krate::models::WriteDataPoint for #ident #ty_generics #where_clause
+            {
+                fn write_data_point_to<W>(&self,mut w: W) -> std::io::Result<()>
+                where
+                    W: std::io::Write{
+                    let mut fields = Vec::new();
+                    #writable_krate::FlattenWritable::encode_fields_into(self, &mut fields)?;
+                    // A line without fields is invalid, so skip the point.
+                    if fields.is_empty() {
+                        return Ok(());
+                    }
+                    let mut tags = Vec::new();
+                    #writable_krate::FlattenWritable::encode_tags_into(self, &mut tags)?;
+                    // Sorted by key like `DataPoint` does, which the server prefers
+                    tags.sort_by(|a, b| a.0.cmp(&b.0));
 
-    let mut combined_fields_writes = vec![];
-    for (index, fields_write) in fields_writes.iter().enumerate() {
-        if index > 0 {
-            combined_fields_writes.push(quote!(w.write_all(b",")?;));
+                    #measurement_write
+                    for (key, value) in &tags {
+                        w.write_all(b",")?;
+                        w.write_all(key.as_bytes())?;
+                        w.write_all(b"=")?;
+                        w.write_all(value.as_bytes())?;
+                    }
+                    w.write_all(b" ")?;
+                    for (index, (key, value)) in fields.iter().enumerate() {
+                        if index > 0 {
+                            w.write_all(b",")?;
+                        }
+                        w.write_all(key.as_bytes())?;
+                        w.write_all(b"=")?;
+                        w.write_all(value.as_bytes())?;
+                    }
+                    #(
+                        #timestamp_writes
+                    )*
+                    w.write_all(b"\n")?;
+
+                    Ok(())
+                }
+            }
         }
-        combined_fields_writes.push(fields_write.clone());
-    }
+    } else {
+        quote!()
+    };
 
     let output = quote! {
-        impl #generics #krate::models::WriteDataPoint for #ident #generics
+        impl #impl_generics #writable_krate::FlattenWritable for #ident #ty_generics #where_clause
         {
-            fn write_data_point_to<W>(&self,mut w: W) -> std::io::Result<()>
-            where
-                W: std::io::Write{
-                w.write_all(format!("{},", #measure).as_bytes())?;
-
+            fn encode_tags_into(&self, tags: &mut Vec<(String, String)>) -> std::io::Result<()> {
                 #(
-                    #combined_tag_writes
+                    #tag_writes
                 )*
-                w.write_all(b" ")?;
-                #(
-                    #combined_fields_writes
-                )*
-                w.write_all(b" ")?;
+                Ok(())
+            }
+
+            fn encode_fields_into(&self, fields: &mut Vec<(String, String)>) -> std::io::Result<()> {
                 #(
-                    #timestamp_writes
+                    #fields_writes
                 )*
-                w.write_all(b"\n")?;
-
                 Ok(())
             }
         }
+
+        #write_data_point
     };
     output.into()
 }
@@ -164,49 +238,109 @@ struct FieldWritable {
     field_type: FieldType,
     kind: Type,
     ident: Ident,
+    /// Tag or field key, the field name unless renamed
+    key: String,
+    encoding: Encoding,
+    /// `TimestampPrecision` variant of a timestamp
+    precision: Option<Ident>,
 }
 
 impl FieldWritable {
-    fn from(value: Field) -> Option<Self> {
+    fn from(value: Field) -> Self {
         let ident = value.ident.unwrap();
+        let mut field_type = None;
+        let mut key = ident.unraw().to_string();
+        let mut encoding = Encoding::Value;
+        let mut precision = None;
 
-        let field_type = value.attrs.iter().find_map(|a| {
-            if a.path.is_ident(INFLUX_TAG) {
-                a.parse_meta().ok().and_then(|meta| match meta {
-                    Meta::List(list) => {
-                        if list.path.is_ident(INFLUX_TAG) {
-                            list.nested.iter().find_map(|m| match m {
-                                syn::NestedMeta::Meta(Meta::Path(p)) => {
-                                    if p.is_ident("tag") {
-                                        Some(FieldType::Tag)
-                                    } else if p.is_ident("ignore") {
-                                        Some(FieldType::Ignore)
-                                    } else if p.is_ident("field") {
-                                        Some(FieldType::Field)
-                                    } else if p.is_ident("timestamp") {
-                                        Some(FieldType::Timestamp)
-                                    } else {
-                                        None
-                                    }
-                                }
-                                _ => None,
-                            })
-                        } else {
-                            None
+        let nested = value
+            .attrs
+            .iter()
+            .filter(|a| a.path.is_ident(INFLUX_TAG))
+            .filter_map(|a| match a.parse_meta() {
+                Ok(Meta::List(list)) => Some(list.nested),
+                _ => None,
+            })
+            .flatten();
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::Path(p)) => {
+                    let name = path_name(&p);
+                    match name.as_str() {
+                        "tag" => field_type = Some(FieldType::Tag),
+                        "field" => field_type = Some(FieldType::Field),
+                        "timestamp" => field_type = Some(FieldType::Timestamp),
+                        "ignore" | "skip" => field_type = Some(FieldType::Ignore),
+                        // Only used by `FromDataPoint`
+                        "default" => {}
+                        "measurement" => field_type = Some(FieldType::Measurement),
+                        "flatten" => field_type = Some(FieldType::Flatten),
+                        "display" => encoding = Encoding::Display,
+                        "serde" => encoding = Encoding::Serde,
+                        _ => panic!("Unknown attribute #[influxdb({})] on `{}`", name, ident),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) => {
+                    let name = path_name(&path);
+                    match name.as_str() {
+                        "rename" => key = lit.value(),
+                        "with" => {
+                            let path = lit.parse::<Path>().unwrap_or_else(|_| {
+                                panic!("#[influxdb(with)] on `{}` must be a path", ident)
+                            });
+                            encoding = Encoding::With(path)
+                        }
+                        "precision" => {
+                            let variant = match lit.value().as_str() {
+                                "s" => "Seconds",
+                                "ms" => "Milliseconds",
+                                "us" => "Microseconds",
+                                "ns" => "Nanoseconds",
+                                other => panic!(
+                                    "Unknown precision `{}` on `{}`, expected s, ms, us or ns",
+                                    other, ident
+                                ),
+                            };
+                            precision = Some(Ident::new(variant, lit.span()))
                         }
+                        _ => panic!("Unknown attribute #[influxdb({} = ...)] on `{}`", name, ident),
                     }
-                    _ => None,
-                })
-            } else {
-                None
+                }
+                _ => panic!("Unsupported #[influxdb] attribute on `{}`", ident),
             }
-        });
-        Some(Self {
+        }
+
+        Self {
             field_type: field_type.unwrap_or(FieldType::Field),
             kind: value.ty,
-            ident: ident,
-        })
+            ident,
+            key,
+            encoding,
+            precision,
+        }
     }
+
+    /// Expression of the tag or field value to encode
+    fn value(&self) -> TokenStream2 {
+        let ident = &self.ident;
+        match &self.encoding {
+            Encoding::Value => quote!(self.#ident),
+            Encoding::Display => quote!(self.#ident.to_string()),
+            Encoding::Serde => {
+                let writable_krate = writable_krate();
+                quote!(#writable_krate::serde_string(&self.#ident)?)
+            }
+            Encoding::With(path) => quote!(#path(&self.#ident)),
+        }
+    }
+}
+
+fn path_name(path: &Path) -> String {
+    path.get_ident().map(Ident::to_string).unwrap_or_default()
 }
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Debug)]
@@ -215,4 +349,19 @@ enum FieldType {
     Field,
     Timestamp,
     Ignore,
+    Measurement,
+    Flatten,
+}
+
+/// How a tag or field value is turned into something writable
+#[derive(Debug)]
+enum Encoding {
+    /// The value itself
+    Value,
+    /// `Display` of the value
+    Display,
+    /// serde serialization of the value
+    Serde,
+    /// The result of a function taking a reference to the value
+    With(Path),
 }
@@ -0,0 +1,9 @@
+use influxdb2_derive::FromDataPoint;
+
+#[derive(FromDataPoint)]
+struct Request {
+    path: String,
+    elapsed: std::time::Duration,
+}
+
+fn main() {}
@@ -28,6 +28,19 @@ struct Item2 {
     #[influxdb(timestamp)]
     time: u64,
 }
+#[derive(WriteDataPoint)]
+#[measurement = "something"]
+struct Item3 {
+    #[influxdb(tag)]
+    name: Option<String>,
+    #[influxdb(field)]
+    field1: Option<u64>,
+    #[influxdb(field)]
+    field2: Option<String>,
+    #[influxdb(timestamp)]
+    time: u64,
+}
+
 fn main() {
     use influxdb2::models::WriteDataPoint;
     use std::io::Write;
@@ -63,6 +76,28 @@ fn main() {
     println!("{}", std::str::from_utf8(&writer).unwrap());
     assert_eq!(
         &writer[..],
-        b"something,name=foo,name2=None field1=\"None\",field2=33i 222222\n"
-    )
+        b"something,name=foo field2=33i 222222\n"
+    );
+
+    let item = Item3 {
+        name: None,
+        field1: None,
+        field2: None,
+        time: 222222u64,
+    };
+
+    let mut writer = Vec::new();
+    item.write_data_point_to(&mut writer).unwrap();
+    assert!(writer.is_empty());
+
+    let item = Item3 {
+        name: None,
+        field1: None,
+        field2: Some("x".to_string()),
+        time: 222222u64,
+    };
+
+    let mut writer = Vec::new();
+    item.write_data_point_to(&mut writer).unwrap();
+    assert_eq!(&writer[..], b"something field2=\"x\" 222222\n");
 }
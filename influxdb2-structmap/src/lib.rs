@@ -4,16 +4,53 @@
 pub mod value;
 
 use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
 
 // Alias for BTreeMap with String keys and generic values
 pub type GenericMap = BTreeMap<String, value::Value>;
 
-pub trait FromMap: Default {
+pub trait FromMap: Sized {
     /// Converts a `GenericMap` back into a structure.
-    /// __Constraints__: assumes that value types conform to the original types of the struct.
-    fn from_genericmap(map: GenericMap) -> Self;
+    /// __Constraints__: fails if a key is missing or its value has a different type than the
+    /// struct field.
+    fn from_genericmap(map: GenericMap) -> Result<Self, FromMapError>;
 }
 
+/// Error returned when a `GenericMap` can't be converted into a structure.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FromMapError {
+    /// The map has no entry for a field.
+    MissingKey {
+        /// The key of the field
+        key: String,
+    },
+    /// The value of a key has a different type than its field.
+    TypeMismatch {
+        /// The key of the field
+        key: String,
+        /// The type of the field
+        expected: &'static str,
+        /// The value in the map
+        found: value::Value,
+    },
+}
+
+impl fmt::Display for FromMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingKey { key } => write!(f, "Missing key `{}`", key),
+            Self::TypeMismatch {
+                key,
+                expected,
+                found,
+            } => write!(f, "Expected {} for key `{}`, found {:?}", expected, key, found),
+        }
+    }
+}
+
+impl Error for FromMapError {}
+
 pub trait ToMap: Default {
     /// Generates a `GenericMap` where value types are all encapsulated under a sum type.
     /// __Constraints__: currently only supports primitive types for genericized values.
@@ -0,0 +1,274 @@
+//! Arrow
+//!
+//! Query results as Apache Arrow record batches, with the `arrow` feature.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ::arrow::array::{
+    ArrayRef, BinaryBuilder, BooleanBuilder, DurationNanosecondBuilder, Float64Builder,
+    Int64Builder, StringBuilder, TimestampNanosecondBuilder, UInt64Builder,
+};
+use ::arrow::datatypes::{DataType as ArrowType, Field, Schema, TimeUnit};
+use ::arrow::record_batch::RecordBatch;
+use fallible_iterator::FallibleIterator;
+use influxdb2_structmap::value::Value;
+
+use crate::api::query::{DataType, FluxRecord, FluxTableMetadata};
+use crate::models::Query;
+use crate::{Client, RequestError};
+
+impl Client {
+    /// Query, returning one record batch per table of the result
+    ///
+    /// The schema of each batch follows the `#datatype` annotations of the
+    /// table, every column being nullable. The schema metadata holds the
+    /// position of the table under `table` and the names of its group key
+    /// columns, as a JSON array, under `group_key`.
+    pub fn query_arrow(&self, query: Option<Query>) -> Result<Vec<RecordBatch>, RequestError> {
+        let mut qtr = self.query_records(query)?;
+        let mut batches = Vec::new();
+        let mut table: Option<TableBuilder> = None;
+        while let Some(record) = qtr.next()? {
+            if qtr.table_changed {
+                if let Some(table) = table.take() {
+                    batches.push(table.finish()?);
+                }
+                let metadata = qtr.table.as_ref().expect("a record belongs to a table");
+                table = Some(TableBuilder::new(metadata));
+            }
+            table
+                .as_mut()
+                .expect("a table starts with its first record")
+                .append(record)?;
+        }
+        if let Some(table) = table {
+            batches.push(table.finish()?);
+        }
+        Ok(batches)
+    }
+}
+
+/// Arrow type of the values of a column
+pub fn arrow_type(data_type: DataType) -> ArrowType {
+    match data_type {
+        DataType::String => ArrowType::Utf8,
+        DataType::Double => ArrowType::Float64,
+        DataType::Bool => ArrowType::Boolean,
+        DataType::Long => ArrowType::Int64,
+        DataType::UnsignedLong => ArrowType::UInt64,
+        DataType::Duration => ArrowType::Duration(TimeUnit::Nanosecond),
+        DataType::Base64Binary => ArrowType::Binary,
+        DataType::TimeRFC => ArrowType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+    }
+}
+
+struct TableBuilder {
+    schema: Schema,
+    columns: Vec<(String, ColumnBuilder)>,
+}
+
+impl TableBuilder {
+    fn new(metadata: &FluxTableMetadata) -> Self {
+        let fields: Vec<Field> = metadata
+            .columns
+            .iter()
+            .map(|column| Field::new(&column.name, arrow_type(column.data_type), true))
+            .collect();
+        let group_key: Vec<&str> = metadata
+            .columns
+            .iter()
+            .filter(|column| column.group)
+            .map(|column| column.name.as_str())
+            .collect();
+
+        let mut schema_metadata = HashMap::new();
+        schema_metadata.insert("table".to_string(), metadata.position.to_string());
+        schema_metadata.insert(
+            "group_key".to_string(),
+            serde_json::to_string(&group_key).expect("names serialize to JSON"),
+        );
+
+        Self {
+            schema: Schema::new_with_metadata(fields, schema_metadata),
+            columns: metadata
+                .columns
+                .iter()
+                .map(|column| (column.name.clone(), ColumnBuilder::new(column.data_type)))
+                .collect(),
+        }
+    }
+
+    fn append(&mut self, mut record: FluxRecord) -> Result<(), RequestError> {
+        for (name, column) in &mut self.columns {
+            let value = record
+                .values
+                .remove(name.as_str())
+                .unwrap_or(Value::Unknown);
+            column
+                .append(value)
+                .map_err(|reason| RequestError::Deserializing {
+                    text: format!("invalid value in column `{}`: {}", name, reason),
+                })?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<RecordBatch, RequestError> {
+        let arrays = self
+            .columns
+            .into_iter()
+            .map(|(_, column)| column.finish())
+            .collect();
+        RecordBatch::try_new(Arc::new(self.schema), arrays).map_err(|e| {
+            RequestError::Deserializing {
+                text: e.to_string(),
+            }
+        })
+    }
+}
+
+enum ColumnBuilder {
+    String(StringBuilder),
+    Double(Float64Builder),
+    Bool(BooleanBuilder),
+    Long(Int64Builder),
+    UnsignedLong(UInt64Builder),
+    Duration(DurationNanosecondBuilder),
+    Base64Binary(BinaryBuilder),
+    Time(TimestampNanosecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: DataType) -> Self {
+        match data_type {
+            DataType::String => Self::String(StringBuilder::new()),
+            DataType::Double => Self::Double(Float64Builder::new()),
+            DataType::Bool => Self::Bool(BooleanBuilder::new()),
+            DataType::Long => Self::Long(Int64Builder::new()),
+            DataType::UnsignedLong => Self::UnsignedLong(UInt64Builder::new()),
+            DataType::Duration => Self::Duration(DurationNanosecondBuilder::new()),
+            DataType::Base64Binary => Self::Base64Binary(BinaryBuilder::new()),
+            DataType::TimeRFC => Self::Time(TimestampNanosecondBuilder::new().with_timezone("UTC")),
+        }
+    }
+
+    // Values are parsed by the type of their column, so any other value is
+    // a missing one.
+    fn append(&mut self, value: Value) -> Result<(), String> {
+        match (self, value) {
+            (Self::String(b), Value::String(v)) => b.append_value(v),
+            (Self::Double(b), Value::Double(v)) => b.append_value(v.into_inner()),
+            (Self::Bool(b), Value::Bool(v)) => b.append_value(v),
+            (Self::Long(b), Value::Long(v)) => b.append_value(v),
+            (Self::UnsignedLong(b), Value::UnsignedLong(v)) => b.append_value(v),
+            (Self::Duration(b), Value::Duration(v)) => match v.num_nanoseconds() {
+                Some(nanos) => b.append_value(nanos),
+                None => return Err(format!("duration {} overflows nanoseconds", v)),
+            },
+            (Self::Base64Binary(b), Value::Base64Binary(v)) => b.append_value(v),
+            (Self::Time(b), Value::TimeRFC(v)) => match v.timestamp_nanos_opt() {
+                Some(nanos) => b.append_value(nanos),
+                None => return Err(format!("time {} overflows nanoseconds", v)),
+            },
+            (Self::String(b), _) => b.append_null(),
+            (Self::Double(b), _) => b.append_null(),
+            (Self::Bool(b), _) => b.append_null(),
+            (Self::Long(b), _) => b.append_null(),
+            (Self::UnsignedLong(b), _) => b.append_null(),
+            (Self::Duration(b), _) => b.append_null(),
+            (Self::Base64Binary(b), _) => b.append_null(),
+            (Self::Time(b), _) => b.append_null(),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            Self::String(mut b) => Arc::new(b.finish()),
+            Self::Double(mut b) => Arc::new(b.finish()),
+            Self::Bool(mut b) => Arc::new(b.finish()),
+            Self::Long(mut b) => Arc::new(b.finish()),
+            Self::UnsignedLong(mut b) => Arc::new(b.finish()),
+            Self::Duration(mut b) => Arc::new(b.finish()),
+            Self::Base64Binary(mut b) => Arc::new(b.finish()),
+            Self::Time(mut b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::arrow::array::{Array, Float64Array, Int64Array, StringArray, TimestampNanosecondArray};
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn query_arrow() {
+        let text = "#datatype,string,long,dateTime:RFC3339,string,string,double
+#group,false,false,false,true,true,false
+#default,_result,,,,,
+,result,table,_time,host,_field,_value
+,,0,2021-01-01T00:00:00Z,a,usage,1.5
+,,0,2021-01-01T00:00:01.5Z,a,usage,
+,,1,2021-01-01T00:00:00Z,b,usage,2.5
+
+#datatype,string,long,string,long
+#group,false,false,true,false
+#default,_result,,,
+,result,table,_field,_value
+,,2,count,7
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let batches = client.query_arrow(None).unwrap();
+        mock_server.assert();
+
+        assert_eq!(batches.len(), 3);
+        let first = &batches[0];
+        let schema = first.schema();
+        assert_eq!(schema.metadata()["table"], "0");
+        assert_eq!(schema.metadata()["group_key"], r#"["host","_field"]"#);
+        assert_eq!(
+            schema.field_with_name("_time").unwrap().data_type(),
+            &ArrowType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into()))
+        );
+        assert_eq!(first.num_rows(), 2);
+
+        let time = first
+            .column_by_name("_time")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<TimestampNanosecondArray>()
+            .unwrap();
+        assert_eq!(time.value(1), 1_609_459_201_500_000_000);
+        let value = first
+            .column_by_name("_value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(value.value(0), 1.5);
+        assert!(value.is_null(1));
+
+        let host = batches[1]
+            .column_by_name("host")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(host.value(0), "b");
+
+        let count = batches[2]
+            .column_by_name("_value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(count.value(0), 7);
+    }
+}
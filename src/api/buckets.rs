@@ -3,7 +3,7 @@
 use serde::{Deserialize, Serialize};
 use snafu::ResultExt;
 
-use crate::models::{Buckets, PostBucketRequest};
+use crate::models::{Buckets, MeasurementSchemaList, PostBucketRequest, WriteSchema};
 use crate::{Client, Http, RequestError, UreqProcessing};
 
 impl Client {
@@ -71,6 +71,41 @@ impl Client {
         }
         Ok(())
     }
+
+    /// List the explicit measurement schemas of the bucket specified by
+    /// bucket id. Only buckets with the `explicit` schema type have any.
+    pub fn list_measurement_schemas(
+        &self,
+        bucket_id: &str,
+    ) -> Result<MeasurementSchemaList, RequestError> {
+        let url = self.url(&format!("/api/v2/buckets/{}/schema/measurements", bucket_id))?;
+
+        let response = self
+            .get(url)
+            .query("org", &self.org)
+            .call()
+            .context(UreqProcessing)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.into_body().read_to_string().context(UreqProcessing)?;
+            return Http { status, text }.fail();
+        }
+
+        let res = response
+            .into_body()
+            .read_json::<MeasurementSchemaList>()
+            .context(UreqProcessing)?;
+
+        Ok(res)
+    }
+
+    /// Build a `WriteSchema` from the explicit measurement schemas of the
+    /// bucket specified by bucket id.
+    pub fn bucket_write_schema(&self, bucket_id: &str) -> Result<WriteSchema, RequestError> {
+        let schemas = self.list_measurement_schemas(bucket_id)?;
+        Ok(WriteSchema::from(&schemas))
+    }
 }
 
 /// Request for list buckets API
@@ -98,7 +133,7 @@ pub struct ListBucketsRequest {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockito::mock;
+    use mockito::{mock, Matcher};
 
     fn setup() -> (Client, String, String) {
         let org_id = "0000111100001111".to_string();
@@ -108,6 +143,7 @@ mod tests {
         (client, org_id, token)
     }
 
+    #[test]
     fn create_bucket() {
         let (client, org_id, token) = setup();
 
@@ -115,13 +151,10 @@ mod tests {
 
         let mock_server = mock("POST", "/api/v2/buckets")
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body(
-                format!(
-                    r#"{{"orgID":"{}","name":"{}","retentionRules":[]}}"#,
-                    org_id, bucket
-                )
-                .as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"orgID":"{}","name":"{}","retentionRules":[]}}"#,
+                org_id, bucket
+            )))
             .create();
 
         let _result = client
@@ -131,6 +164,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn list_buckets_with_params() {
         let (client, _, token) = setup();
 
@@ -155,6 +189,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn list_buckets_without_params() {
         let (client, _, token) = setup();
 
@@ -166,4 +201,31 @@ mod tests {
 
         mock_server.assert();
     }
+
+    #[test]
+    fn list_measurement_schemas() {
+        let (client, org, token) = setup();
+        let bucket_id = "0000222200002222";
+
+        let mock_server = mock(
+            "GET",
+            format!("/api/v2/buckets/{bucket_id}/schema/measurements?org={org}").as_str(),
+        )
+        .match_header("Authorization", format!("Token {}", token).as_str())
+        .with_body(
+            r#"{"measurementSchemas":[{"name":"cpu","columns":[{"name":"host","type":"tag"}]}]}"#,
+        )
+        .create();
+
+        let schema = client.bucket_write_schema(bucket_id).unwrap();
+
+        mock_server.assert();
+        assert_eq!(
+            schema,
+            WriteSchema::new().measurement(
+                "cpu",
+                crate::models::MeasurementWriteSchema::new().tag("host")
+            )
+        );
+    }
 }
@@ -0,0 +1,433 @@
+//! Cancel
+//!
+//! Cancellation of running queries from another thread.
+
+use std::cell::RefCell;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use ureq::unversioned::resolver::DefaultResolver;
+use ureq::unversioned::transport::{
+    Buffers, ConnectProxyConnector, ConnectionDetails, Connector, Either, LazyBuffers,
+    NextTimeout, RustlsConnector, Transport,
+};
+use ureq::{Agent, BodyReader, ProxyProtocol};
+
+use crate::RequestError;
+
+/// Cancels the queries it is set on, see `Query::with_cancellation`
+///
+/// Clones share their state, so that one can be kept to call `cancel` from
+/// another thread while the query runs. Cancelling closes the connections
+/// of the queries and makes them return `RequestError::Cancelled`, even if
+/// the response is already being read. A cancelled token stays cancelled,
+/// and any query started with it fails at once.
+///
+/// # Example
+///
+/// ```no_run
+/// use influxdb2::api::cancel::CancellationToken;
+/// use influxdb2::models::Query;
+/// use influxdb2::{Client, RequestError};
+///
+/// let client = Client::new("http://localhost:8086", "org", "token");
+/// let token = CancellationToken::new();
+/// let query = Query::new(r#"from(bucket: "cpu") |> range(start: -1y)"#.to_string())
+///     .with_cancellation(token.clone());
+///
+/// let handle = std::thread::spawn(move || client.query_raw(Some(query)));
+/// token.cancel();
+/// assert!(matches!(handle.join().unwrap(), Err(RequestError::Cancelled)));
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    next_id: u64,
+    // Clones of the sockets of the connections still open
+    streams: Vec<(u64, TcpStream)>,
+}
+
+impl CancellationToken {
+    /// Create a token that isn't cancelled yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the queries using this token
+    pub fn cancel(&self) {
+        let mut state = self.state.lock();
+        state.cancelled = true;
+        for (_, stream) in state.streams.drain(..) {
+            // The connection may be closed already
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Has `cancel` been called?
+    pub fn is_cancelled(&self) -> bool {
+        self.state.lock().cancelled
+    }
+
+    /// Run `send` on a request of the agent from `agent()`, so that the
+    /// connections it opens are closed on `cancel`.
+    pub(crate) fn send<T>(&self, send: impl FnOnce() -> T) -> T {
+        struct Reset;
+
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                SENDING.with(|token| token.replace(None));
+            }
+        }
+
+        SENDING.with(|token| token.replace(Some(self.clone())));
+        let _reset = Reset;
+        send()
+    }
+
+    fn register(&self, stream: &TcpStream) -> io::Result<u64> {
+        let mut state = self.state.lock();
+        if state.cancelled {
+            return Err(cancelled());
+        }
+        state.next_id += 1;
+        let id = state.next_id;
+        state.streams.push((id, stream.try_clone()?));
+        Ok(id)
+    }
+
+    fn unregister(&self, id: u64) {
+        self.state.lock().streams.retain(|(i, _)| *i != id);
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+thread_local! {
+    // Token of the query this thread is sending, see `CancellationToken::send`
+    static SENDING: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/// Agent for queries with a `CancellationToken`, each `Client` has one.
+///
+/// A connection belongs to the token of the query that opened it, so they
+/// aren't kept to be reused by other queries. Like ureq's default agent, it
+/// goes through the proxy of the environment; SOCKS proxies are refused.
+pub(crate) fn agent() -> Agent {
+    let config = Agent::config_builder().max_idle_connections(0).build();
+    let connector = ()
+        .chain(ConnectProxyConnector::default())
+        .chain(CancellableConnector)
+        .chain(RustlsConnector::default());
+    Agent::with_parts(config, connector, DefaultResolver::default())
+}
+
+// The IO error of a cancelled query, turned into `RequestError::Cancelled`
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the query was cancelled")
+    }
+}
+
+impl StdError for Cancelled {}
+
+fn cancelled() -> io::Error {
+    io::Error::other(Cancelled)
+}
+
+/// Error of reading the body of a query response
+pub(crate) fn body_error(source: io::Error) -> RequestError {
+    if source.get_ref().is_some_and(|e| e.is::<Cancelled>()) {
+        RequestError::Cancelled
+    } else {
+        RequestError::BodyReading { source }
+    }
+}
+
+/// Error of sending a query, `token` being the one of the query
+pub(crate) fn request_error(source: ureq::Error, token: Option<&CancellationToken>) -> RequestError {
+    if token.is_some_and(CancellationToken::is_cancelled) {
+        RequestError::Cancelled
+    } else {
+        RequestError::UreqProcessing { source }
+    }
+}
+
+/// Body of a query response, failing once the query is cancelled
+pub(crate) struct QueryBody {
+    reader: BodyReader<'static>,
+    token: Option<CancellationToken>,
+}
+
+impl QueryBody {
+    pub(crate) fn new(reader: BodyReader<'static>, token: Option<CancellationToken>) -> Self {
+        Self { reader, token }
+    }
+}
+
+impl Read for QueryBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let token = self.token.as_ref();
+        if token.is_some_and(CancellationToken::is_cancelled) {
+            return Err(cancelled());
+        }
+        // Reading from a closed connection fails, or ends the body early.
+        match self.reader.read(buf) {
+            Err(_) | Ok(0) if token.is_some_and(CancellationToken::is_cancelled) => {
+                Err(cancelled())
+            }
+            result => result,
+        }
+    }
+}
+
+// Opens TCP connections that the token of the query being sent can close.
+// A tunnel through a proxy is passed on, its own connection to the proxy
+// having been opened here.
+#[derive(Debug)]
+struct CancellableConnector;
+
+impl<In: Transport> Connector<In> for CancellableConnector {
+    type Out = Either<In, CancellableTransport>;
+
+    fn connect(
+        &self,
+        details: &ConnectionDetails<'_>,
+        chained: Option<In>,
+    ) -> Result<Option<Self::Out>, ureq::Error> {
+        if let Some(transport) = chained {
+            return Ok(Some(Either::A(transport)));
+        }
+        if let Some(proxy) = details.config.proxy() {
+            if matches!(
+                proxy.protocol(),
+                ProxyProtocol::Socks4 | ProxyProtocol::Socks4A | ProxyProtocol::Socks5
+            ) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "queries with a cancellation token can't go through a SOCKS proxy",
+                )
+                .into());
+            }
+        }
+        let token = match SENDING.with(|token| token.borrow().clone()) {
+            Some(token) => token,
+            None => return Err(io::Error::other("the query has no cancellation token").into()),
+        };
+
+        let mut last_error = io::Error::new(io::ErrorKind::ConnectionRefused, "Connection refused");
+        for addr in &details.addrs {
+            let stream = match details.timeout.not_zero() {
+                Some(timeout) => TcpStream::connect_timeout(addr, *timeout),
+                None => TcpStream::connect(addr),
+            };
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+            if details.config.no_delay() {
+                stream.set_nodelay(true)?;
+            }
+            let id = token.register(&stream)?;
+            let buffers = LazyBuffers::new(
+                details.config.input_buffer_size(),
+                details.config.output_buffer_size(),
+            );
+            return Ok(Some(Either::B(CancellableTransport {
+                stream,
+                buffers,
+                token,
+                id,
+            })));
+        }
+        Err(last_error.into())
+    }
+}
+
+// A TCP connection, like the one of ureq's `TcpConnector`
+#[derive(Debug)]
+struct CancellableTransport {
+    stream: TcpStream,
+    buffers: LazyBuffers,
+    token: CancellationToken,
+    id: u64,
+}
+
+// Timeouts show as `WouldBlock` on some platforms and `TimedOut` on others
+fn io_error(e: io::Error, timeout: NextTimeout) -> ureq::Error {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ureq::Error::Timeout(timeout.reason),
+        _ => e.into(),
+    }
+}
+
+impl Transport for CancellableTransport {
+    fn buffers(&mut self) -> &mut dyn Buffers {
+        &mut self.buffers
+    }
+
+    fn transmit_output(&mut self, amount: usize, timeout: NextTimeout) -> Result<(), ureq::Error> {
+        self.stream.set_write_timeout(timeout.not_zero().map(|t| *t))?;
+        let output = &self.buffers.output()[..amount];
+        self.stream
+            .write_all(output)
+            .map_err(|e| io_error(e, timeout))
+    }
+
+    fn await_input(&mut self, timeout: NextTimeout) -> Result<bool, ureq::Error> {
+        self.stream.set_read_timeout(timeout.not_zero().map(|t| *t))?;
+        let input = self.buffers.input_append_buf();
+        let amount = self.stream.read(input).map_err(|e| io_error(e, timeout))?;
+        self.buffers.input_appended(amount);
+        Ok(amount > 0)
+    }
+
+    // A connection to reuse has nothing to read, reading would block.
+    fn is_open(&mut self) -> bool {
+        if self.token.is_cancelled() || self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let open = matches!(
+            self.stream.read(&mut [0]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock
+        );
+        self.stream.set_nonblocking(false).is_ok() && open
+    }
+}
+
+impl Drop for CancellableTransport {
+    fn drop(&mut self) {
+        self.token.unregister(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Query;
+    use crate::Client;
+    use fallible_iterator::FallibleIterator;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // Server answering one request with `response`, then keeping the
+    // connection open without sending anything more
+    fn stalling_server(response: &'static str) -> (String, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            // Read the body, until the client closes the connection
+            while let Ok(1..) = reader.read(&mut [0; 1024]) {}
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn cancelled_before_sending() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let client = Client::new("http://127.0.0.1:9", "org", "token");
+        let query = Query::default().with_cancellation(token);
+        assert!(matches!(client.query_raw(Some(query)), Err(RequestError::Cancelled)));
+    }
+
+    #[test]
+    fn cancel_while_waiting_for_the_response() {
+        let (url, server) = stalling_server("");
+        let token = CancellationToken::new();
+        let query = Query::default().with_cancellation(token.clone());
+        let client = Client::new(url, "org", "token");
+        let start = Instant::now();
+        let query = thread::spawn(move || client.query_raw(Some(query)));
+
+        thread::sleep(Duration::from_millis(100));
+        token.cancel();
+        assert!(matches!(query.join().unwrap(), Err(RequestError::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn cancel_while_streaming() {
+        let (url, server) = stalling_server(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             22\r\n,result,table,_value\n,_result,0,1\n\r\n",
+        );
+        let token = CancellationToken::new();
+        let mut query = Query::default();
+        query.dialect = None;
+        let query = query
+            .with_cancellation(token.clone())
+            .with_timeout(Duration::from_secs(30));
+        let client = Client::new(url, "org", "token");
+        let mut records = client.query_raw_stream(Some(query)).unwrap();
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        });
+        let mut result = records.next();
+        // The first record may be parsed before the cancellation
+        if let Ok(Some(_)) = result {
+            result = records.next();
+        }
+        assert!(matches!(result, Err(RequestError::Cancelled)), "{:?}", result);
+        canceller.join().unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let (url, server) = stalling_server("");
+        let client = Client::new(url, "org", "token");
+        let query = Query::default().with_timeout(Duration::from_millis(100));
+        let result = client.query_raw(Some(query));
+        assert!(
+            matches!(
+                result,
+                Err(RequestError::UreqProcessing {
+                    source: ureq::Error::Timeout(_)
+                })
+            ),
+            "{:?}",
+            result
+        );
+        drop(client);
+        server.join().unwrap();
+    }
+}
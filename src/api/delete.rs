@@ -16,7 +16,7 @@ impl Client {
     ///
     /// fn foo() {
     ///     let client = Client::new("some-host", "some-org", "some-token");
-    ///     let start = NaiveDate::from_ymd(2020, 1, 1).and_hms(0, 0, 0);
+    ///     let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
     ///     let stop = NaiveDate::from_ymd(2020, 12, 31).and_hms(23, 59, 59);
     ///     let predicate = Some("_measurement=\"some-measurement\"".to_owned());
     ///     client.delete("some-bucket", start, stop, predicate).unwrap();
@@ -62,6 +62,7 @@ mod tests {
     use chrono::NaiveDate;
     use mockito::mock;
 
+    #[test]
     fn delete_points() {
         let org = "some-org";
         let bucket = "some-bucket";
@@ -79,8 +80,8 @@ mod tests {
 
         let client = Client::new(mockito::server_url(), org, token);
 
-        let start = NaiveDate::from_ymd(2020, 1, 1).and_hms(0, 0, 0);
-        let stop = NaiveDate::from_ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let stop = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
         let _result = client.delete(bucket, start, stop, None);
 
         mock_server.assert();
@@ -38,6 +38,7 @@ mod tests {
     use super::*;
     use mockito::mock;
 
+    #[test]
     fn health() {
         let mock_server = mock("GET", "/health").create();
 
@@ -131,10 +131,11 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockito::mock;
+    use mockito::{mock, Matcher};
 
     const BASE_PATH: &str = "/api/v2/labels";
 
+    #[test]
     fn labels() {
         let token = "some-token";
 
@@ -149,6 +150,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn labels_by_org() {
         let token = "some-token";
         let org_id = "some-org_id";
@@ -164,6 +166,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn find_label() {
         let token = "some-token";
         let label_id = "some-id";
@@ -179,6 +182,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn create_label() {
         let token = "some-token";
         let org_id = "some-org";
@@ -188,13 +192,10 @@ mod tests {
 
         let mock_server = mock("POST", BASE_PATH)
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body(
-                format!(
-                    r#"{{"orgID":"{}","name":"{}","properties":{{"some-key":"some-value"}}}}"#,
-                    org_id, name
-                )
-                .as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"orgID":"{}","name":"{}","properties":{{"some-key":"some-value"}}}}"#,
+                org_id, name
+            )))
             .create();
 
         let client = Client::new(mockito::server_url(), org_id, token);
@@ -204,6 +205,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn create_label_opt() {
         let token = "some-token";
         let org_id = "some-org_id";
@@ -211,7 +213,7 @@ mod tests {
 
         let mock_server = mock("POST", BASE_PATH)
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body(format!(r#"{{"orgID":"{}","name":"{}"}}"#, org_id, name).as_str())
+            .match_body(Matcher::JsonString(format!(r#"{{"orgID":"{}","name":"{}"}}"#, org_id, name)))
             .create();
 
         let client = Client::new(mockito::server_url(), org_id, token);
@@ -221,6 +223,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn update_label() {
         let token = "some-token";
         let name = "some-user";
@@ -230,13 +233,10 @@ mod tests {
 
         let mock_server = mock("PATCH", format!("{}/{}", BASE_PATH, label_id).as_str())
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body(
-                format!(
-                    r#"{{"name":"{}","properties":{{"some-key":"some-value"}}}}"#,
-                    name
-                )
-                .as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"name":"{}","properties":{{"some-key":"some-value"}}}}"#,
+                name
+            )))
             .create();
 
         let client = Client::new(mockito::server_url(), "", token);
@@ -248,13 +248,14 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn update_label_opt() {
         let token = "some-token";
         let label_id = "some-label_id";
 
         let mock_server = mock("PATCH", format!("{}/{}", BASE_PATH, label_id).as_str())
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body("{}")
+            .match_body(Matcher::JsonString("{}".to_string()))
             .create();
 
         let client = Client::new(mockito::server_url(), "", token);
@@ -264,6 +265,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn delete_label() {
         let token = "some-token";
         let label_id = "some-label_id";
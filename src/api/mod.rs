@@ -1,10 +1,15 @@
 //! InfluxDB v2.0 Client API
+#[cfg(feature = "arrow")]
+pub mod arrow;
 pub mod authorizations;
 pub mod buckets;
+pub mod cancel;
 pub mod delete;
 pub mod health;
 pub mod label;
 pub mod organization;
+#[cfg(feature = "polars")]
+pub mod polars;
 pub mod query;
 pub mod ready;
 pub mod setup;
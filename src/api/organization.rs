@@ -66,6 +66,7 @@ mod tests {
     use super::*;
     use mockito::mock;
 
+    #[test]
     fn list_buckets() {
         let org_id = "0000111100001111".to_string();
         let token = "some-token";
@@ -0,0 +1,422 @@
+//! Polars
+//!
+//! Query results as, and writes from, Polars data frames, with the `polars`
+//! feature.
+
+use std::collections::HashMap;
+
+use ::polars::prelude::{
+    polars_bail, AnyValue, Column, DataFrame, DataType as PolarsType, IntoColumn, NamedFrom,
+    PolarsResult, Series, TimeUnit, TimeZone,
+};
+use influxdb2_structmap::value::Value;
+use influxdb2_structmap::GenericMap;
+use snafu::ResultExt;
+
+use crate::api::query::{DataType, Pivot};
+use crate::models::{DataPoint, FieldValue, Query};
+use crate::{Client, DataFrameConversion, RequestError};
+
+impl Client {
+    /// Query, returning the result as a data frame
+    ///
+    /// Fields are merged into columns with `Pivot::Auto`.
+    pub fn query_df(&self, query: Option<Query>) -> Result<DataFrame, RequestError> {
+        self.query_df_with_pivot(query, &Pivot::Auto)
+    }
+
+    /// Query, returning the result as a data frame with records merged into
+    /// rows as `pivot` says
+    ///
+    /// Every column of the rows becomes a column of the frame, in the order
+    /// they are first seen. Once pivoted, the `_field` and `_value` columns
+    /// are dropped. Times are `Datetime` columns in nanoseconds and UTC.
+    pub fn query_df_with_pivot(
+        &self,
+        query: Option<Query>,
+        pivot: &Pivot,
+    ) -> Result<DataFrame, RequestError> {
+        let mut rows = self.query_rows(query, pivot)?;
+        if *pivot != Pivot::Off {
+            for row in &mut rows {
+                if row.remove("_field").is_some() {
+                    row.remove("_value");
+                }
+            }
+        }
+        data_frame(&rows)
+    }
+
+    /// Write every row of `df` as a point of `measurement` to the specified
+    /// bucket.
+    ///
+    /// `tag_columns` are written as tags and `time_column`, a `Datetime` or
+    /// an `Int64` of nanoseconds, as the timestamp. Every other column is a
+    /// field, and has to be a boolean, integer, float or string column.
+    /// Null and empty tags and null fields are left out of their point. A
+    /// row with a null time fails the write.
+    pub fn write_df(
+        &self,
+        bucket: &str,
+        df: &DataFrame,
+        measurement: &str,
+        tag_columns: &[&str],
+        time_column: Option<&str>,
+    ) -> Result<(), RequestError> {
+        let points = data_points(df, measurement, tag_columns, time_column)
+            .context(DataFrameConversion)?;
+        self.write(bucket, points)
+    }
+}
+
+/// Polars type of the values of a column
+pub fn polars_type(data_type: DataType) -> PolarsType {
+    match data_type {
+        DataType::String => PolarsType::String,
+        DataType::Double => PolarsType::Float64,
+        DataType::Bool => PolarsType::Boolean,
+        DataType::Long => PolarsType::Int64,
+        DataType::UnsignedLong => PolarsType::UInt64,
+        DataType::Duration => PolarsType::Duration(TimeUnit::Nanoseconds),
+        DataType::Base64Binary => PolarsType::Binary,
+        DataType::TimeRFC => PolarsType::Datetime(TimeUnit::Nanoseconds, Some(TimeZone::UTC)),
+    }
+}
+
+fn data_frame(rows: &[GenericMap]) -> Result<DataFrame, RequestError> {
+    // The type of a column is the one of its first value, rows may come from
+    // tables with different columns.
+    let mut names: Vec<&str> = Vec::new();
+    let mut types: HashMap<&str, Option<DataType>> = HashMap::new();
+    for row in rows {
+        for (name, value) in row {
+            let data_type = types.entry(name.as_str()).or_insert_with(|| {
+                names.push(name.as_str());
+                None
+            });
+            if data_type.is_none() {
+                *data_type = value_type(value);
+            }
+        }
+    }
+
+    let columns = names
+        .into_iter()
+        .map(|name| {
+            // A column without any value is null in every row.
+            let data_type = types[name].unwrap_or(DataType::String);
+            let mut column = ColumnBuilder::new(data_type, rows.len());
+            for row in rows {
+                let value = row.get(name).unwrap_or(&Value::Unknown);
+                if !column.append(value) {
+                    return Err(RequestError::Deserializing {
+                        text: format!(
+                            "column `{}` mixes {:?} with a value of another type",
+                            name, data_type
+                        ),
+                    });
+                }
+            }
+            column.finish(name).context(DataFrameConversion)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    DataFrame::new(columns).context(DataFrameConversion)
+}
+
+fn value_type(value: &Value) -> Option<DataType> {
+    match value {
+        Value::Unknown => None,
+        Value::String(_) => Some(DataType::String),
+        Value::Double(_) => Some(DataType::Double),
+        Value::Bool(_) => Some(DataType::Bool),
+        Value::Long(_) => Some(DataType::Long),
+        Value::UnsignedLong(_) => Some(DataType::UnsignedLong),
+        Value::Duration(_) => Some(DataType::Duration),
+        Value::Base64Binary(_) => Some(DataType::Base64Binary),
+        Value::TimeRFC(_) => Some(DataType::TimeRFC),
+    }
+}
+
+enum ColumnBuilder {
+    String(Vec<Option<String>>),
+    Double(Vec<Option<f64>>),
+    Bool(Vec<Option<bool>>),
+    Long(Vec<Option<i64>>),
+    UnsignedLong(Vec<Option<u64>>),
+    Duration(Vec<Option<i64>>),
+    Base64Binary(Vec<Option<Vec<u8>>>),
+    Time(Vec<Option<i64>>),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: DataType, capacity: usize) -> Self {
+        match data_type {
+            DataType::String => Self::String(Vec::with_capacity(capacity)),
+            DataType::Double => Self::Double(Vec::with_capacity(capacity)),
+            DataType::Bool => Self::Bool(Vec::with_capacity(capacity)),
+            DataType::Long => Self::Long(Vec::with_capacity(capacity)),
+            DataType::UnsignedLong => Self::UnsignedLong(Vec::with_capacity(capacity)),
+            DataType::Duration => Self::Duration(Vec::with_capacity(capacity)),
+            DataType::Base64Binary => Self::Base64Binary(Vec::with_capacity(capacity)),
+            DataType::TimeRFC => Self::Time(Vec::with_capacity(capacity)),
+        }
+    }
+
+    // Durations and times out of the nanosecond range are null. Returns
+    // false for a value of another type than the column.
+    fn append(&mut self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::String(b), Value::String(v)) => b.push(Some(v.clone())),
+            (Self::Double(b), Value::Double(v)) => b.push(Some(v.into_inner())),
+            (Self::Bool(b), Value::Bool(v)) => b.push(Some(*v)),
+            (Self::Long(b), Value::Long(v)) => b.push(Some(*v)),
+            (Self::UnsignedLong(b), Value::UnsignedLong(v)) => b.push(Some(*v)),
+            (Self::Duration(b), Value::Duration(v)) => b.push(v.num_nanoseconds()),
+            (Self::Base64Binary(b), Value::Base64Binary(v)) => b.push(Some(v.clone())),
+            (Self::Time(b), Value::TimeRFC(v)) => b.push(v.timestamp_nanos_opt()),
+            (Self::String(b), Value::Unknown) => b.push(None),
+            (Self::Double(b), Value::Unknown) => b.push(None),
+            (Self::Bool(b), Value::Unknown) => b.push(None),
+            (Self::Long(b), Value::Unknown) => b.push(None),
+            (Self::UnsignedLong(b), Value::Unknown) => b.push(None),
+            (Self::Duration(b), Value::Unknown) => b.push(None),
+            (Self::Base64Binary(b), Value::Unknown) => b.push(None),
+            (Self::Time(b), Value::Unknown) => b.push(None),
+            _ => return false,
+        }
+        true
+    }
+
+    fn finish(self, name: &str) -> PolarsResult<Column> {
+        let series = match self {
+            Self::String(v) => Series::new(name.into(), v),
+            Self::Double(v) => Series::new(name.into(), v),
+            Self::Bool(v) => Series::new(name.into(), v),
+            Self::Long(v) => Series::new(name.into(), v),
+            Self::UnsignedLong(v) => Series::new(name.into(), v),
+            Self::Duration(v) => Series::new(name.into(), v)
+                .cast(&polars_type(DataType::Duration))?,
+            Self::Base64Binary(v) => Series::new(name.into(), v),
+            Self::Time(v) => Series::new(name.into(), v).cast(&polars_type(DataType::TimeRFC))?,
+        };
+        Ok(series.into_column())
+    }
+}
+
+fn data_points(
+    df: &DataFrame,
+    measurement: &str,
+    tag_columns: &[&str],
+    time_column: Option<&str>,
+) -> PolarsResult<Vec<DataPoint>> {
+    let tags = tag_columns
+        .iter()
+        .map(|&name| df.column(name)?.cast(&PolarsType::String))
+        .collect::<PolarsResult<Vec<_>>>()?;
+    let time = time_column.map(|name| df.column(name)).transpose()?;
+    if let Some(time) = time {
+        match time.dtype() {
+            PolarsType::Datetime(_, _) | PolarsType::Int64 => {}
+            other => polars_bail!(
+                SchemaMismatch: "time column `{}` is {}, not a datetime or an Int64",
+                time.name(), other
+            ),
+        }
+    }
+    let fields = df
+        .get_columns()
+        .iter()
+        .filter(|column| {
+            !tag_columns.contains(&column.name().as_str())
+                && time_column != Some(column.name().as_str())
+        })
+        .collect::<Vec<_>>();
+    for field in &fields {
+        let dtype = field.dtype();
+        if !(dtype.is_bool() || dtype.is_primitive_numeric() || dtype.is_string()) {
+            polars_bail!(
+                SchemaMismatch: "field column `{}` is {}, which line protocol can't hold",
+                field.name(), dtype
+            );
+        }
+    }
+
+    (0..df.height())
+        .map(|i| {
+            let mut builder = DataPoint::builder(measurement);
+            for tag in &tags {
+                match tag.get(i)? {
+                    AnyValue::String(value) if !value.is_empty() => {
+                        builder = builder.tag(tag.name().as_str(), value);
+                    }
+                    _ => {}
+                }
+            }
+            for field in &fields {
+                if let Some(value) = field_value(field.get(i)?) {
+                    builder = builder.field(field.name().as_str(), value);
+                }
+            }
+            if let Some(time) = time {
+                if let Some(nanos) = timestamp(time.get(i)?, i)? {
+                    builder = builder.timestamp(nanos);
+                }
+            }
+            match builder.build() {
+                Ok(point) => Ok(point),
+                Err(_) => polars_bail!(ComputeError: "row {} has no non-null field", i),
+            }
+        })
+        .collect()
+}
+
+fn field_value(value: AnyValue<'_>) -> Option<FieldValue> {
+    match value {
+        AnyValue::Boolean(v) => Some(FieldValue::Bool(v)),
+        AnyValue::Float32(v) => Some(FieldValue::F64(v.into())),
+        AnyValue::Float64(v) => Some(FieldValue::F64(v)),
+        AnyValue::Int8(v) => Some(FieldValue::I64(v.into())),
+        AnyValue::Int16(v) => Some(FieldValue::I64(v.into())),
+        AnyValue::Int32(v) => Some(FieldValue::I64(v.into())),
+        AnyValue::Int64(v) => Some(FieldValue::I64(v)),
+        AnyValue::UInt8(v) => Some(FieldValue::U64(v.into())),
+        AnyValue::UInt16(v) => Some(FieldValue::U64(v.into())),
+        AnyValue::UInt32(v) => Some(FieldValue::U64(v.into())),
+        AnyValue::UInt64(v) => Some(FieldValue::U64(v)),
+        AnyValue::String(v) => Some(FieldValue::String(v.to_string())),
+        AnyValue::StringOwned(v) => Some(FieldValue::String(v.to_string())),
+        _ => None,
+    }
+}
+
+// Nanoseconds since the epoch, whatever the unit and time zone of the column
+fn timestamp(value: AnyValue<'_>, row: usize) -> PolarsResult<Option<i64>> {
+    let (v, nanos) = match value {
+        AnyValue::Datetime(v, unit, _) | AnyValue::DatetimeOwned(v, unit, _) => match unit {
+            TimeUnit::Nanoseconds => (v, 1),
+            TimeUnit::Microseconds => (v, 1_000),
+            TimeUnit::Milliseconds => (v, 1_000_000),
+        },
+        AnyValue::Int64(v) => (v, 1),
+        AnyValue::Null => polars_bail!(ComputeError: "time of row {} is null", row),
+        _ => return Ok(None),
+    };
+    match v.checked_mul(nanos) {
+        Some(v) => Ok(Some(v)),
+        None => polars_bail!(
+            ComputeError: "time of row {} is out of the range of nanosecond timestamps", row
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::polars::prelude::df;
+    use mockito::{mock, Matcher};
+
+    #[test]
+    fn query_df() {
+        let text = "#datatype,string,long,dateTime:RFC3339,string,string,double
+#group,false,false,false,true,true,false
+#default,_result,,,,,
+,result,table,_time,host,_field,_value
+,,0,2021-01-01T00:00:00Z,a,usage,1.5
+,,0,2021-01-01T00:00:01Z,a,usage,
+
+#datatype,string,long,dateTime:RFC3339,string,string,string
+#group,false,false,false,true,true,false
+#default,_result,,,,,
+,result,table,_time,host,_field,_value
+,,1,2021-01-01T00:00:00Z,a,status,ok
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let df = client.query_df(None).unwrap();
+        mock_server.assert();
+
+        assert_eq!(df.height(), 2);
+        assert!(df.column("_field").is_err());
+        assert!(df.column("_value").is_err());
+        assert_eq!(
+            df.column("_time").unwrap().dtype(),
+            &PolarsType::Datetime(TimeUnit::Nanoseconds, Some(TimeZone::UTC))
+        );
+        let usage = df.column("usage").unwrap().f64().unwrap();
+        assert_eq!(usage.get(0), Some(1.5));
+        assert_eq!(usage.get(1), None);
+        let status = df.column("status").unwrap().str().unwrap();
+        assert_eq!(status.get(0), Some("ok"));
+        assert_eq!(status.get(1), None);
+    }
+
+    #[test]
+    fn write_df() {
+        let df = df!(
+            "host" => [Some("a"), Some("")],
+            "time" => [1_609_459_200_000_000_000i64, 1_609_459_201_000_000_000],
+            "usage" => [Some(1.5), None],
+            "count" => [1u32, 2],
+        )
+        .unwrap();
+        let mock_server = mock("POST", "/api/v2/write")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("bucket".into(), "some-bucket".into()),
+                Matcher::UrlEncoded("org".into(), "some-org".into()),
+                Matcher::UrlEncoded("precision".into(), "ns".into()),
+            ]))
+            .match_body(
+                "cpu,host=a count=1u,usage=1.5 1609459200000000000
+cpu count=2u 1609459201000000000
+",
+            )
+            .with_status(204)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        client
+            .write_df("some-bucket", &df, "cpu", &["host"], Some("time"))
+            .unwrap();
+        mock_server.assert();
+    }
+
+    #[test]
+    fn write_df_rejects_unsupported_fields() {
+        let df = df!("blob" => [Some(vec![1u8, 2])]).unwrap();
+        let client = Client::new("http://localhost:8086", "some-org", "some-token");
+        let error = client
+            .write_df("some-bucket", &df, "cpu", &[], None)
+            .unwrap_err();
+        assert!(matches!(error, RequestError::DataFrameConversion { .. }));
+    }
+
+    #[test]
+    fn write_df_rejects_out_of_range_times() {
+        let mut df = df!("time" => [i64::MAX / 1_000], "usage" => [1.5]).unwrap();
+        let time = df.column("time").unwrap();
+        let time = time
+            .cast(&PolarsType::Datetime(TimeUnit::Milliseconds, None))
+            .unwrap();
+        df.with_column(time).unwrap();
+        let client = Client::new("http://localhost:8086", "some-org", "some-token");
+        let error = client
+            .write_df("some-bucket", &df, "cpu", &[], Some("time"))
+            .unwrap_err();
+        assert!(error.to_string().contains("row 0"), "{}", error);
+    }
+
+    #[test]
+    fn write_df_rejects_null_times() {
+        let df = df!("time" => [Some(1i64), None], "usage" => [1.5, 2.5]).unwrap();
+        let client = Client::new("http://localhost:8086", "some-org", "some-token");
+        let error = client
+            .write_df("some-bucket", &df, "cpu", &[], Some("time"))
+            .unwrap_err();
+        assert!(error.to_string().contains("row 1 is null"), "{}", error);
+    }
+}
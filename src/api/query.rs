@@ -3,43 +3,110 @@
 //! Query InfluxDB using InfluxQL or Flux Query
 
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::marker::PhantomData;
 use std::str::FromStr;
 
-use crate::{Client, Http, RequestError, UreqProcessing};
+use crate::api::cancel::{body_error, request_error, CancellationToken, QueryBody};
+use crate::{
+    de, Client, Http, RecordDeserializing, RecordMapping, RequestError, UreqProcessing,
+};
 
 use base64::decode;
-use chrono::DateTime;
+use chrono::{DateTime, FixedOffset};
 use csv::StringRecord;
 use fallible_iterator::FallibleIterator;
 use go_parse_duration::parse_duration;
 use influxdb2_structmap::value::Value;
 use influxdb2_structmap::{FromMap, GenericMap};
 use ordered_float::OrderedFloat;
+use serde::de::DeserializeOwned;
 use snafu::ResultExt;
 use ureq::http::StatusCode;
+use crate::models::ast::dialect::Annotations;
+use crate::models::ast::{lint, Diagnostic, Dialect};
 use crate::models::{
-    AnalyzeQueryResponse, AstResponse, FluxSuggestion, FluxSuggestions, LanguageRequest, Query,
+    AnalyzeQueryResponse, AstResponse, FluxSuggestion, FluxSuggestions, LanguageRequest,
+    MeasurementWriteSchema, Query, WriteSchema,
 };
 
 /// Allows for multiple iterators over the result
 #[derive(Clone, Debug)]
 pub struct QueryTableIter {
     text: String,
+    format: CsvFormat,
 }
 
 impl<'a> QueryTableIter {
-    fn new(text: String) -> Self {
-        Self { text }
+    fn new(text: String, format: CsvFormat) -> Self {
+        Self { text, format }
     }
 
     /// Get the iterator
     pub fn result(&'a self) -> impl FallibleIterator<Item = FluxRecord, Error = RequestError> + 'a {
-        QueryTableResult::new(&self.text)
+        QueryTableResult::with_format(self.text.as_bytes(), self.format.clone())
     }
 
     /// Is the response empty?
     pub fn is_empty(&self) -> bool {
-        matches!(QueryTableResult::new(&self.text).next(), Ok(None))
+        matches!(self.result().next(), Ok(None))
+    }
+}
+
+/// Records of a query result, parsed while the response is read
+///
+/// Only the current row is held in memory. Dropping the stream closes the
+/// response.
+pub struct QueryRecordStream {
+    records: QueryTableResult<QueryBody>,
+}
+
+impl std::fmt::Debug for QueryRecordStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryRecordStream").finish()
+    }
+}
+
+impl FallibleIterator for QueryRecordStream {
+    type Item = FluxRecord;
+    type Error = RequestError;
+
+    fn next(&mut self) -> Result<Option<FluxRecord>, RequestError> {
+        self.records.next()
+    }
+}
+
+/// Rows of a query result, mapped to `T` while the response is read
+///
+/// Every record becomes a row on its own, with its `_value` also available
+/// under the name of its `_field`. Unlike `Client::query`, fields from
+/// different tables are not merged, use `pivot()` in the query for that.
+pub struct QueryStream<T> {
+    records: QueryRecordStream,
+    row: PhantomData<fn() -> T>,
+}
+
+impl<T> std::fmt::Debug for QueryStream<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryStream").finish()
+    }
+}
+
+impl<T: FromMap> FallibleIterator for QueryStream<T> {
+    type Item = T;
+    type Error = RequestError;
+
+    fn next(&mut self) -> Result<Option<T>, RequestError> {
+        match self.records.next()? {
+            Some(record) => {
+                let mut values = record.values;
+                if let Some((field, value)) = field_value(&values) {
+                    values.insert(field, value);
+                }
+                T::from_genericmap(values).context(RecordMapping).map(Some)
+            }
+            None => Ok(None),
+        }
     }
 }
 
@@ -89,88 +156,188 @@ impl Client {
     }
 
     /// Query
+    ///
+    /// Fields are merged into rows with `Pivot::Auto`.
     pub fn query<T: FromMap>(&self, query: Option<Query>) -> Result<Vec<T>, RequestError> {
-        let req_url = self.url("/api/v2/query")?;
+        self.query_with_pivot(query, &Pivot::Auto)
+    }
 
-        let response = self
-            .post(req_url)
-            .header("Accepting-Encoding", "identity")
-            .header("Content-Type", "application/json")
-            .query("org", &self.org)
-            .send_json(query.unwrap_or_default())
-            .context(UreqProcessing)?;
+    /// Query, merging records into rows as `pivot` says
+    pub fn query_with_pivot<T: FromMap>(
+        &self,
+        query: Option<Query>,
+        pivot: &Pivot,
+    ) -> Result<Vec<T>, RequestError> {
+        self.query_rows(query, pivot)?
+            .into_iter()
+            .map(|row| T::from_genericmap(row).context(RecordMapping))
+            .collect()
+    }
 
-        match response.status() {
-            StatusCode::OK => {
-                let text = response.into_body().read_to_string().unwrap();
-                let qtr = QueryTableResult::new(&text[..]);
-                let qr = QueryResult::new(qtr)?;
-                let mut res = vec![];
-                for item in qr.items {
-                    res.push(T::from_genericmap(item));
-                }
-                Ok(res)
-            }
-            status => {
-                let text = response.into_body().read_to_string().context(UreqProcessing)?;
-                Http { status, text }.fail()?
-            }
-        }
+    /// Query, deserializing each row with serde.
+    ///
+    /// See the `de` module for how columns map to the fields of `T`.
+    pub fn query_as<T: DeserializeOwned>(
+        &self,
+        query: Option<Query>,
+    ) -> Result<Vec<T>, RequestError> {
+        self.query_rows(query, &Pivot::Auto)?
+            .into_iter()
+            .map(|row| de::from_genericmap(row).context(RecordDeserializing))
+            .collect()
+    }
+
+    // Rows of the result, with the `_field` and `_value` of each table pivoted
+    // into columns.
+    pub(crate) fn query_rows(
+        &self,
+        query: Option<Query>,
+        pivot: &Pivot,
+    ) -> Result<Vec<GenericMap>, RequestError> {
+        let qtr = self.query_records(query)?;
+        Ok(QueryResult::new(qtr.iterator(), pivot)?.items)
     }
 
     /// Query Raw
     pub fn query_raw(&self, query: Option<Query>) -> Result<Vec<FluxRecord>, RequestError> {
-        let req_url = self.url("/api/v2/query")?;
+        self.query_raw_stream(query)?.collect()
+    }
 
-        let response = self
-            .post(req_url)
-            .header("Accepting-Encoding", "identity")
-            .header("Content-Type", "application/json")
-            .query("org", &self.org)
-            .send_json(query.unwrap_or_default())
-            .context(UreqProcessing)?;
+    /// Query return an iterator over the raw results
+    ///
+    /// The whole response is kept in memory so that it can be iterated more
+    /// than once, see `query_raw_stream` for large results.
+    pub fn query_raw_iter(
+        &self,
+        query: Option<Query>,
+    ) -> Result<QueryTableIter, RequestError> {
+        let format = CsvFormat::of(query.as_ref())?;
+        let mut text = String::new();
+        self.query_body(query)?
+            .read_to_string(&mut text)
+            .map_err(body_error)?;
+        Ok(QueryTableIter::new(text, format))
+    }
 
-        match response.status() {
-            StatusCode::OK => {
-                let text = response.into_body().read_to_string().unwrap();
-                let qtr = QueryTableResult::new(&text[..]);
-                let mut records = vec![];
-                for record in qtr.iterator() {
-                    records.push(record?);
-                }
-                Ok(records)
+    /// Query, returning the result as tables with their column schema
+    pub fn query_tables(&self, query: Option<Query>) -> Result<Vec<FluxTable>, RequestError> {
+        let mut qtr = self.query_records(query)?;
+        let mut tables: Vec<FluxTable> = vec![];
+        while let Some(record) = qtr.next()? {
+            if qtr.table_changed {
+                let metadata = qtr.table.as_ref().expect("a record belongs to a table");
+                let result = match record.get("result") {
+                    Some(Value::String(result)) => result.clone(),
+                    _ => String::new(),
+                };
+                tables.push(FluxTable {
+                    result,
+                    position: metadata.position,
+                    columns: metadata.columns.clone(),
+                    records: vec![],
+                });
             }
-            status => {
-                let text = response.into_body().read_to_string().context(UreqProcessing)?;
-                Http { status, text }.fail()?
+            if let Some(table) = tables.last_mut() {
+                table.records.push(record);
             }
         }
+        Ok(tables)
     }
 
-    /// Query return an iterator over the raw results
-    /// Saves on memory usage
-    pub fn query_raw_iter(
+    /// Query, returning the tables of each result by name
+    ///
+    /// A script with several `yield(name: ...)` calls returns one result per
+    /// name, e.g. `mean` and `max`. `rows` maps the tables of a result to a
+    /// row type.
+    pub fn query_results(
         &self,
         query: Option<Query>,
-    ) -> Result<QueryTableIter, RequestError> {
+    ) -> Result<HashMap<String, Vec<FluxTable>>, RequestError> {
+        let mut results: HashMap<String, Vec<FluxTable>> = HashMap::new();
+        for table in self.query_tables(query)? {
+            results.entry(table.result.clone()).or_default().push(table);
+        }
+        Ok(results)
+    }
+
+    /// Query, returning the raw records as they are read from the response
+    ///
+    /// Memory use doesn't grow with the size of the result, and dropping the
+    /// stream stops reading it.
+    pub fn query_raw_stream(
+        &self,
+        query: Option<Query>,
+    ) -> Result<QueryRecordStream, RequestError> {
+        Ok(QueryRecordStream {
+            records: self.query_records(query)?,
+        })
+    }
+
+    /// Query, mapping each record to `T` as it is read from the response
+    ///
+    /// See `QueryStream` for how records become rows.
+    pub fn query_stream<T: FromMap>(
+        &self,
+        query: Option<Query>,
+    ) -> Result<QueryStream<T>, RequestError> {
+        Ok(QueryStream {
+            records: self.query_raw_stream(query)?,
+            row: PhantomData,
+        })
+    }
+
+    /// Query, returning the response body as it is read, without parsing it
+    ///
+    /// The body is the CSV written after the `Dialect` of the query, e.g. to
+    /// pass it through to another client.
+    pub fn query_csv_raw(&self, query: Option<Query>) -> Result<impl Read + Send, RequestError> {
+        self.query_body(query)
+    }
+
+    // Records of a successful query response, parsed after the dialect of
+    // the query
+    pub(crate) fn query_records(
+        &self,
+        query: Option<Query>,
+    ) -> Result<QueryTableResult<QueryBody>, RequestError> {
+        let format = CsvFormat::of(query.as_ref())?;
+        Ok(QueryTableResult::with_format(self.query_body(query)?, format))
+    }
+
+    // Body of a successful query response, the annotated CSV result
+    pub(crate) fn query_body(&self, query: Option<Query>) -> Result<QueryBody, RequestError> {
         let req_url = self.url("/api/v2/query")?;
+        let query = query.unwrap_or_default();
+        let token = query.cancellation().cloned();
+        if token.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(RequestError::Cancelled);
+        }
 
-        let response = self
-            .post(req_url)
+        let mut request = match &token {
+            Some(_) => self.with_auth(self.cancellable.post(req_url)),
+            None => self.post(req_url),
+        };
+        if let Some(timeout) = query.timeout() {
+            request = request.config().timeout_global(Some(timeout)).build();
+        }
+        let request = request
             .header("Accepting-Encoding", "identity")
             .header("Content-Type", "application/json")
-            .query("org", &self.org)
-            .send_json(query.unwrap_or_default())
-            .context(UreqProcessing)?;
+            .query("org", &self.org);
+        let send = || request.send_json(&query);
+        let response = match &token {
+            Some(token) => token.send(send),
+            None => send(),
+        }
+            .map_err(|source| request_error(source, token.as_ref()))?;
 
         match response.status() {
-            StatusCode::OK => {
-                let text = response.into_body().read_to_string().context(UreqProcessing)?;
-
-                Ok(QueryTableIter::new(text))
-            }
+            StatusCode::OK => Ok(QueryBody::new(response.into_body().into_reader(), token)),
             status => {
-                let text = response.into_body().read_to_string().context(UreqProcessing)?;
+                let text = response
+                    .into_body()
+                    .read_to_string()
+                    .map_err(|source| request_error(source, token.as_ref()))?;
                 Http { status, text }.fail()?
             }
         }
@@ -226,6 +393,22 @@ impl Client {
         }
     }
 
+    /// Checks a query before running it
+    ///
+    /// Returns the errors found by `query_analyze`. When there are none, the
+    /// script is also parsed with `query_ast` and checked by
+    /// `models::ast::lint` for unbounded scans, unfiltered scans and yield
+    /// names used twice.
+    pub fn lint_query(&self, query: &Query) -> Result<Vec<Diagnostic>, RequestError> {
+        let analysis = self.query_analyze(Some(query.clone()))?;
+        if !analysis.errors.is_empty() {
+            return Ok(analysis.errors.iter().map(Diagnostic::from).collect());
+        }
+
+        let ast = self.query_ast(Some(LanguageRequest::new(query.query.clone())))?;
+        Ok(ast.ast.as_ref().map(lint).unwrap_or_default())
+    }
+
     /// Returns bucket measurements
     ///
     /// # Arguments
@@ -239,22 +422,8 @@ impl Client {
         start: Option<&str>,
         stop: Option<&str>,
     ) -> Result<Vec<String>, RequestError> {
-        let mut params = vec![];
-        params.push(format!(r#"bucket: "{bucket}""#));
-        if let Some(start) = start {
-            params.push(format!("start: {start}"));
-        }
-        if let Some(stop) = stop {
-            params.push(format!("stop: {stop}"));
-        }
-        let params = params.join(", ");
-
-        let query = Query::new(format!(
-            r#"import "influxdata/influxdb/schema"
-
-            schema.measurements({params})"#
-        ));
-        self.exec_schema_query(query)
+        let query = Query::new(String::new()).with_param("bucket", bucket);
+        self.exec_schema_query("measurements", query, start, stop)
     }
 
     /// List field keys for measurement
@@ -272,23 +441,10 @@ impl Client {
         start: Option<&str>,
         stop: Option<&str>,
     ) -> Result<Vec<String>, RequestError> {
-        let mut params = vec![];
-        params.push(format!(r#"bucket: "{bucket}""#));
-        params.push(format!(r#"measurement: "{measurement}""#));
-        if let Some(start) = start {
-            params.push(format!("start: {start}"));
-        }
-        if let Some(stop) = stop {
-            params.push(format!("stop: {stop}"));
-        }
-        let params = params.join(", ");
-
-        let query = Query::new(format!(
-            r#"import "influxdata/influxdb/schema"
-
-            schema.measurementFieldKeys({params})"#,
-        ));
-        self.exec_schema_query(query)
+        let query = Query::new(String::new())
+            .with_param("bucket", bucket)
+            .with_param("measurement", measurement);
+        self.exec_schema_query("measurementFieldKeys", query, start, stop)
     }
 
     /// List all tag values for measurement tag
@@ -308,24 +464,11 @@ impl Client {
         start: Option<&str>,
         stop: Option<&str>,
     ) -> Result<Vec<String>, RequestError> {
-        let mut params = vec![];
-        params.push(format!(r#"bucket: "{bucket}""#));
-        params.push(format!(r#"measurement: "{measurement}""#));
-        params.push(format!(r#"tag: "{tag}""#));
-        if let Some(start) = start {
-            params.push(format!("start: {start}"));
-        }
-        if let Some(stop) = stop {
-            params.push(format!("stop: {stop}"));
-        }
-        let params = params.join(", ");
-
-        let query = Query::new(format!(
-            r#"import "influxdata/influxdb/schema"
-
-            schema.measurementTagValues({params})"#,
-        ));
-        self.exec_schema_query(query)
+        let query = Query::new(String::new())
+            .with_param("bucket", bucket)
+            .with_param("measurement", measurement)
+            .with_param("tag", tag);
+        self.exec_schema_query("measurementTagValues", query, start, stop)
     }
 
     /// List all tag keys for measurement
@@ -343,26 +486,72 @@ impl Client {
         start: Option<&str>,
         stop: Option<&str>,
     ) -> Result<Vec<String>, RequestError> {
-        let mut params = vec![];
-        params.push(format!(r#"bucket: "{bucket}""#));
-        params.push(format!(r#"measurement: "{measurement}""#));
+        let query = Query::new(String::new())
+            .with_param("bucket", bucket)
+            .with_param("measurement", measurement);
+        self.exec_schema_query("measurementTagKeys", query, start, stop)
+    }
+
+    /// Build a `WriteSchema` from the measurements, tag keys and field keys
+    /// found in a bucket. Field types aren't reported by the schema
+    /// functions, so fields accept values of any type.
+    ///
+    /// # Arguments
+    ///
+    /// * `bucket` - The bucket name
+    /// * `start` - Optional start time. Default is `-30d`
+    /// * `stop` - Optional stop time. Default is `now()`
+    pub fn infer_write_schema(
+        &self,
+        bucket: &str,
+        start: Option<&str>,
+        stop: Option<&str>,
+    ) -> Result<WriteSchema, RequestError> {
+        let mut schema = WriteSchema::new();
+        for measurement in self.list_measurements(bucket, start, stop)? {
+            let mut measurement_schema = MeasurementWriteSchema::new();
+            for tag in self.list_measurement_tag_keys(bucket, &measurement, start, stop)? {
+                // skip `_measurement`, `_field`, `_start` and `_stop`
+                if !tag.starts_with('_') {
+                    measurement_schema = measurement_schema.tag(tag);
+                }
+            }
+            for field in self.list_measurement_field_keys(bucket, &measurement, start, stop)? {
+                measurement_schema = measurement_schema.untyped_field(field);
+            }
+            schema = schema.measurement(measurement, measurement_schema);
+        }
+        Ok(schema)
+    }
+
+    // Call a function of the `schema` package with the parameters of `query`
+    // as arguments. `start` and `stop` are Flux expressions, e.g. `-30d`.
+    fn exec_schema_query(
+        &self,
+        function: &str,
+        mut query: Query,
+        start: Option<&str>,
+        stop: Option<&str>,
+    ) -> Result<Vec<String>, RequestError> {
+        let mut args: Vec<String> = query
+            .params
+            .iter()
+            .flat_map(|params| params.keys())
+            .map(|name| format!("{name}: params.{name}"))
+            .collect();
         if let Some(start) = start {
-            params.push(format!("start: {start}"));
+            args.push(format!("start: {start}"));
         }
         if let Some(stop) = stop {
-            params.push(format!("stop: {stop}"));
+            args.push(format!("stop: {stop}"));
         }
-        let params = params.join(", ");
-
-        let query = Query::new(format!(
+        query.query = format!(
             r#"import "influxdata/influxdb/schema"
 
-            schema.measurementTagKeys({params})"#,
-        ));
-        self.exec_schema_query(query)
-    }
+            schema.{function}({args})"#,
+            args = args.join(", ")
+        );
 
-    fn exec_schema_query(&self, query: Query) -> Result<Vec<String>, RequestError> {
         let req_url = self.url("/api/v2/query")?;
 
         let response = self
@@ -375,7 +564,7 @@ impl Client {
 
         match response.status() {
             StatusCode::OK => {
-                let text: String = response.into_body().read_to_string().unwrap();
+                let text = response.into_body().read_to_string().context(UreqProcessing)?;
 
                 let mut reader = csv::ReaderBuilder::new()
                     .has_headers(true)
@@ -396,15 +585,24 @@ impl Client {
     }
 }
 
+/// Data type of a column, from the `#datatype` annotation
 #[derive(Clone, Copy, Debug, PartialEq)]
-enum DataType {
+pub enum DataType {
+    /// `string`
     String,
+    /// `double`
     Double,
+    /// `boolean`
     Bool,
+    /// `long`
     Long,
+    /// `unsignedLong`
     UnsignedLong,
+    /// `duration`
     Duration,
+    /// `base64Binary`
     Base64Binary,
+    /// `dateTime:RFC3339` or `dateTime:RFC3339Nano`
     TimeRFC,
 }
 
@@ -429,11 +627,51 @@ impl FromStr for DataType {
     }
 }
 
-struct FluxColumn {
-    name: String,
-    data_type: DataType,
-    group: bool,
-    default_value: String,
+/// Column of a flux table
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxColumn {
+    /// Column name
+    pub name: String,
+    /// Data type of the values
+    pub data_type: DataType,
+    /// Whether the column is part of the group key
+    pub group: bool,
+    /// Value used when a row leaves the column empty
+    pub default_value: String,
+}
+
+/// Represents a flux table returned from a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxTable {
+    /// Name of the result the table belongs to, as given to `yield()`
+    pub result: String,
+    /// Position of the table in the result
+    pub position: i32,
+    /// Columns of the table
+    pub columns: Vec<FluxColumn>,
+    /// Records of the table
+    pub records: Vec<FluxRecord>,
+}
+
+impl FluxTable {
+    /// Columns that are part of the group key
+    pub fn group_key_columns(&self) -> impl Iterator<Item = &FluxColumn> {
+        self.columns.iter().filter(|column| column.group)
+    }
+
+    /// Values of the group key columns, which every record of the table
+    /// shares. Empty for a table without records.
+    pub fn group_key(&self) -> GenericMap {
+        let mut key = GenericMap::new();
+        if let Some(record) = self.records.first() {
+            for column in self.group_key_columns() {
+                if let Some(value) = record.values.get(&column.name) {
+                    key.insert(column.name.clone(), value.clone());
+                }
+            }
+        }
+        key
+    }
 }
 
 /// Represents a flux record returned from a query.
@@ -445,16 +683,135 @@ pub struct FluxRecord {
     pub values: GenericMap,
 }
 
-struct FluxTableMetadata {
-    position: i32,
-    columns: Vec<FluxColumn>,
+impl FluxRecord {
+    /// Value of a column
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.values.get(column)
+    }
+
+    /// `_time` of the record
+    pub fn time(&self) -> Option<DateTime<FixedOffset>> {
+        self.time_column("_time")
+    }
+
+    /// `_start` of the queried range
+    pub fn start(&self) -> Option<DateTime<FixedOffset>> {
+        self.time_column("_start")
+    }
+
+    /// `_stop` of the queried range
+    pub fn stop(&self) -> Option<DateTime<FixedOffset>> {
+        self.time_column("_stop")
+    }
+
+    /// `_value` of the record
+    pub fn value(&self) -> Option<&Value> {
+        self.get("_value")
+    }
+
+    /// `_field` of the record
+    pub fn field(&self) -> Option<&str> {
+        self.string_column("_field")
+    }
+
+    /// `_measurement` of the record
+    pub fn measurement(&self) -> Option<&str> {
+        self.string_column("_measurement")
+    }
+
+    fn time_column(&self, column: &str) -> Option<DateTime<FixedOffset>> {
+        match self.get(column) {
+            Some(Value::TimeRFC(time)) => Some(*time),
+            _ => None,
+        }
+    }
+
+    fn string_column(&self, column: &str) -> Option<&str> {
+        match self.get(column) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Rows of the given tables, with fields merged the same way as
+/// `Client::query`
+pub fn rows<T: FromMap>(tables: &[FluxTable]) -> Result<Vec<T>, RequestError> {
+    let records = tables
+        .iter()
+        .flat_map(|table| table.records.iter().cloned().map(Ok));
+    QueryResult::new(records, &Pivot::Auto)?
+        .items
+        .into_iter()
+        .map(|row| T::from_genericmap(row).context(RecordMapping))
+        .collect()
+}
+
+pub(crate) struct FluxTableMetadata {
+    pub(crate) position: i32,
+    pub(crate) columns: Vec<FluxColumn>,
+}
+
+/// How the CSV of a query result is written, after the `Dialect` of the
+/// query
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CsvFormat {
+    delimiter: u8,
+    header: bool,
+    annotated: bool,
+    datatype: bool,
+    comment_prefix: Option<String>,
 }
 
-struct QueryTableResult<'a> {
-    csv_reader: csv::Reader<&'a [u8]>,
+impl CsvFormat {
+    /// Format of the result of `query`, the server's default being a header
+    /// row without annotations.
+    pub(crate) fn new(dialect: Option<&Dialect>) -> Result<Self, RequestError> {
+        let dialect = match dialect {
+            Some(dialect) => dialect,
+            None => &Dialect {
+                header: None,
+                delimiter: None,
+                annotations: vec![],
+                comment_prefix: None,
+                date_time_format: None,
+            },
+        };
+        let delimiter = match dialect.delimiter.as_deref() {
+            None => b',',
+            Some(d) if d.len() == 1 => d.as_bytes()[0],
+            Some(d) => {
+                return Err(RequestError::Deserializing {
+                    text: format!("delimiter `{}` is not a single ASCII character", d),
+                })
+            }
+        };
+        Ok(Self {
+            delimiter,
+            header: dialect.header.unwrap_or(true),
+            annotated: !dialect.annotations.is_empty(),
+            datatype: dialect.annotations.contains(&Annotations::Datatype),
+            comment_prefix: dialect.comment_prefix.clone().filter(|p| !p.is_empty()),
+        })
+    }
+
+    fn of(query: Option<&Query>) -> Result<Self, RequestError> {
+        match query {
+            Some(query) => Self::new(query.dialect.as_ref()),
+            None => Self::new(Query::default().dialect.as_ref()),
+        }
+    }
+}
+
+pub(crate) struct QueryTableResult<R> {
+    csv_reader: csv::Reader<R>,
+    format: CsvFormat,
     table_position: i32,
-    table_changed: bool,
-    table: Option<FluxTableMetadata>,
+    pub(crate) table_changed: bool,
+    pub(crate) table: Option<FluxTableMetadata>,
+    // `table` column of the last record, tables sharing their annotations
+    // are told apart by it
+    table_id: Option<Value>,
 }
 
 #[derive(PartialEq)]
@@ -464,22 +821,54 @@ enum ParsingState {
     Error,
 }
 
-impl<'a> QueryTableResult<'a> {
-    fn new(text: &'a str) -> Self {
+impl<R: Read> QueryTableResult<R> {
+    pub(crate) fn with_format(reader: R, format: CsvFormat) -> Self {
         let reader = csv::ReaderBuilder::new()
             .has_headers(false)
             .flexible(true)
-            .from_reader(text.as_bytes());
+            .delimiter(format.delimiter)
+            .from_reader(reader);
         Self {
             csv_reader: reader,
+            format,
             table_position: 0,
             table_changed: false,
             table: None,
+            table_id: None,
         }
     }
+
+    // Start a table of `len` columns, named by their position until a header
+    // row names them
+    fn start_table(&mut self, len: usize) {
+        self.table = Some(FluxTableMetadata {
+            position: self.table_position,
+            columns: (0..len)
+                .map(|i| FluxColumn {
+                    name: i.to_string(),
+                    data_type: DataType::String,
+                    group: false,
+                    default_value: String::from(""),
+                })
+                .collect(),
+        });
+        self.table_position += 1;
+        self.table_changed = true;
+        self.table_id = None;
+    }
 }
 
-impl<'a> FallibleIterator for QueryTableResult<'a> {
+// Without annotations, a header row is told apart from the rows of data by
+// its `table` column, which is a number in the data. Error tables have
+// `error` and `reference` columns instead.
+fn is_header(row: &StringRecord) -> bool {
+    matches!(
+        (row.get(1), row.get(2)),
+        (Some("result"), Some("table")) | (Some("error"), Some("reference"))
+    )
+}
+
+impl<R: Read> FallibleIterator for QueryTableResult<R> {
     type Item = FluxRecord;
     type Error = RequestError;
 
@@ -492,60 +881,73 @@ impl<'a> FallibleIterator for QueryTableResult<'a> {
         let mut parsing_state = ParsingState::Normal;
         let mut data_type_annotation_found = false;
         loop {
-            if !self.csv_reader.read_record(&mut row).unwrap() {
+            if !self.csv_reader.read_record(&mut row).map_err(csv_error)? {
                 // EOF
                 return Ok(None);
             }
             if row.len() <= 1 {
                 continue;
             }
-            if let Some(s) = row.get(0) {
-                if !s.is_empty() && s.chars().nth(0).unwrap() == '#' {
-                    // Finding new table, prepare for annotation parsing
-                    if parsing_state == ParsingState::Normal {
-                        self.table = Some(FluxTableMetadata {
-                            position: self.table_position,
-                            columns: Vec::new(),
+            let first = row.get(0).unwrap();
+            let annotation = matches!(first, "#datatype" | "#group" | "#default");
+            if !annotation && !first.is_empty() {
+                match &self.format.comment_prefix {
+                    Some(prefix) if first.starts_with(prefix.as_str()) => continue,
+                    _ => {
+                        return Err(RequestError::Deserializing {
+                            text: format!("invalid first cell: {}", first),
                         });
-                        self.table_position += 1;
-                        self.table_changed = true;
-                        for _ in 1..row.len() {
-                            self.table.as_mut().unwrap().columns.push(FluxColumn {
-                                name: String::from(""),
-                                data_type: DataType::String,
-                                group: false,
-                                default_value: String::from(""),
-                            });
-                        }
-                        parsing_state = ParsingState::Annotation;
                     }
                 }
             }
+            if annotation && parsing_state == ParsingState::Normal {
+                // Finding new table, prepare for annotation parsing
+                self.start_table(row.len() - 1);
+                parsing_state = ParsingState::Annotation;
+            }
+            if !annotation && parsing_state == ParsingState::Normal && !self.format.annotated {
+                if self.format.header && is_header(&row) {
+                    self.start_table(row.len() - 1);
+                    parsing_state = ParsingState::Annotation;
+                } else if !self.format.header
+                    && self
+                        .table
+                        .as_ref()
+                        .is_none_or(|table| table.columns.len() != row.len() - 1)
+                {
+                    self.start_table(row.len() - 1);
+                }
+            }
             if self.table.is_none() {
                 return Err(RequestError::Deserializing {
-                    text: String::from("annotations not found"),
+                    text: String::from(if self.format.annotated {
+                        "annotations not found"
+                    } else {
+                        "header not found"
+                    }),
                 });
             }
             if row.len() - 1 != self.table.as_ref().unwrap().columns.len() {
                 return Err(RequestError::Deserializing {
                     text: format!(
-                        "row has different number of columns than the table: {} vs {}",
+                        "row has different number of columns than the table on line {}: {} vs {}",
+                        row.position().map_or(0, |p| p.line()),
                         row.len() - 1,
                         self.table.as_ref().unwrap().columns.len(),
                     ),
                 });
             }
-            if let Some(s) = row.get(0) {
-                match s {
-                    "" => {
-                        match parsing_state {
-                            ParsingState::Annotation => {
+            match first {
+                "" => {
+                    match parsing_state {
+                        ParsingState::Annotation => {
+                            if self.format.datatype && !data_type_annotation_found {
+                                return Err(RequestError::Deserializing {
+                                    text: String::from("datatype annotation not found"),
+                                });
+                            }
+                            if self.format.header {
                                 // Parse column name (csv header)
-                                if !data_type_annotation_found {
-                                    return Err(RequestError::Deserializing {
-                                        text: String::from("datatype annotation not found"),
-                                    });
-                                }
                                 if row.get(1).unwrap() == "error" {
                                     parsing_state = ParsingState::Error;
                                 } else {
@@ -558,63 +960,77 @@ impl<'a> FallibleIterator for QueryTableResult<'a> {
                                 }
                                 continue;
                             }
-                            ParsingState::Error => {
-                                let msg = if row.len() > 1 && !row.get(1).unwrap().is_empty() {
-                                    row.get(1).unwrap()
-                                } else {
-                                    "unknown query error"
-                                };
-                                let mut reference = String::from("");
-                                if row.len() > 2 && !row.get(2).unwrap().is_empty() {
-                                    let s = row.get(2).unwrap();
-                                    reference = format!(",{}", s);
-                                }
-                                return Err(RequestError::Deserializing {
-                                    text: format!("{}{}", msg, reference),
-                                });
-                            }
-                            _ => {}
                         }
-                        let mut values = BTreeMap::new();
-                        for i in 1..row.len() {
-                            let column = &self.table.as_mut().unwrap().columns[i - 1];
-                            let mut v = row.get(i).unwrap();
-                            if v.is_empty() {
-                                v = &column.default_value[..];
+                        ParsingState::Error => {
+                            let msg = if row.len() > 1 && !row.get(1).unwrap().is_empty() {
+                                row.get(1).unwrap()
+                            } else {
+                                "unknown query error"
+                            };
+                            let mut reference = String::from("");
+                            if row.len() > 2 && !row.get(2).unwrap().is_empty() {
+                                let s = row.get(2).unwrap();
+                                reference = format!(",{}", s);
                             }
-                            let value = parse_value(v, column.data_type, column.name.as_str())?;
-                            values.entry(column.name.clone()).or_insert(value);
+                            return Err(RequestError::Deserializing {
+                                text: format!("{}{}", msg, reference),
+                            });
                         }
-                        record = FluxRecord {
-                            table: self.table.as_ref().unwrap().position,
-                            values,
-                        };
-                        break;
+                        _ => {}
                     }
-                    "#datatype" => {
-                        data_type_annotation_found = true;
-                        for i in 1..row.len() {
-                            let column = &mut self.table.as_mut().unwrap().columns[i - 1];
-                            let dt = DataType::from_str(row.get(i).unwrap())?;
-                            column.data_type = dt;
+                    let line = row.position().map_or(0, |p| p.line());
+                    let mut values = BTreeMap::new();
+                    for i in 1..row.len() {
+                        let column = &self.table.as_mut().unwrap().columns[i - 1];
+                        let mut v = row.get(i).unwrap();
+                        if v.is_empty() {
+                            v = &column.default_value[..];
                         }
+                        let value = parse_value(v, column.data_type).map_err(|reason| {
+                            RequestError::Deserializing {
+                                text: format!(
+                                    "invalid {:?} value `{}` in column `{}` on line {}: {}",
+                                    column.data_type, v, column.name, line, reason
+                                ),
+                            }
+                        })?;
+                        values.entry(column.name.clone()).or_insert(value);
                     }
-                    "#group" => {
-                        for i in 1..row.len() {
-                            let column = &mut self.table.as_mut().unwrap().columns[i - 1];
-                            column.group = row.get(i).unwrap() == "true";
-                        }
+                    let table_id = values.get("table").cloned();
+                    if self.table_id.is_some() && self.table_id != table_id {
+                        let table = self.table.as_mut().unwrap();
+                        table.position = self.table_position;
+                        self.table_position += 1;
+                        self.table_changed = true;
                     }
-                    "#default" => {
-                        for i in 1..row.len() {
-                            let column = &mut self.table.as_mut().unwrap().columns[i - 1];
-                            column.default_value = String::from(row.get(i).unwrap());
-                        }
+                    self.table_id = table_id;
+                    record = FluxRecord {
+                        table: self.table.as_ref().unwrap().position,
+                        values,
+                    };
+                    break;
+                }
+                "#datatype" => {
+                    data_type_annotation_found = true;
+                    for i in 1..row.len() {
+                        let column = &mut self.table.as_mut().unwrap().columns[i - 1];
+                        // Unknown types are kept as strings
+                        column.data_type = row
+                            .get(i)
+                            .and_then(|dt| DataType::from_str(dt).ok())
+                            .unwrap_or(DataType::String);
                     }
-                    _ => {
-                        return Err(RequestError::Deserializing {
-                            text: format!("invalid first cell: {}", s),
-                        });
+                }
+                "#group" => {
+                    for i in 1..row.len() {
+                        let column = &mut self.table.as_mut().unwrap().columns[i - 1];
+                        column.group = row.get(i).unwrap() == "true";
+                    }
+                }
+                _ => {
+                    for i in 1..row.len() {
+                        let column = &mut self.table.as_mut().unwrap().columns[i - 1];
+                        column.default_value = String::from(row.get(i).unwrap());
                     }
                 }
             }
@@ -623,103 +1039,158 @@ impl<'a> FallibleIterator for QueryTableResult<'a> {
     }
 }
 
+/// How `Client::query_with_pivot` turns records into rows
+///
+/// InfluxDB returns every field of a point as a record of its own, in long
+/// format: the field name is in the `_field` column and its value in
+/// `_value`. Pivoting merges these records into one row per point, with
+/// each value under the name of its field.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Pivot {
+    /// Merge records in long format that match in every column except
+    /// `table`, `_start`, `_stop`, `_field` and `_value`. Records without a
+    /// `_field` column, e.g. after a `pivot()` in the query, are kept as they
+    /// are.
+    #[default]
+    Auto,
+    /// Merge records that match in the given columns, e.g. `_time` and the
+    /// tags. Every record has to be in long format and have these columns.
+    RowKey(Vec<String>),
+    /// Keep one row per record
+    Off,
+}
+
 struct QueryResult {
     items: Vec<GenericMap>,
 }
 
 impl QueryResult {
-    fn new(qtr: QueryTableResult<'_>) -> Result<Self, RequestError> {
-        let ignored_keys = vec!["_field", "_value", "table"];
-        let ignored_keys: HashSet<&str> = ignored_keys.into_iter().collect();
-
-        // Construct build table, this groups values with the same tags and
-        // timestamp but in different table.
+    fn new<I>(records: I, pivot: &Pivot) -> Result<Self, RequestError>
+    where
+        I: IntoIterator<Item = Result<FluxRecord, RequestError>>,
+    {
+        let ignored_keys: HashSet<&str> = ["_field", "_value", "table", "_start", "_stop"]
+            .iter()
+            .copied()
+            .collect();
+
+        // Rows by their key, in the order the key was first seen.
         //
         // We need to do this because influxdb v2 stores multiple fields in
         // different tables even though it's part of the same measurement.
-        let mut build_table = HashMap::<GenericMap, GenericMap>::new();
-        let mut key_order: Vec<GenericMap> = vec![];
-        for record in qtr.iterator() {
-            let mut record_values = record?.values;
-
-            // Construct key
-            let mut key = record_values.clone();
-            key.retain(|k, _| !ignored_keys.contains(k.as_str()));
-
-            match build_table.get_mut(&key) {
-                Some(entry) => {
-                    // Set field value
-                    let field;
-                    if let Value::String(f) = record_values.get("_field").unwrap() {
-                        field = f.clone();
-                    } else {
-                        unreachable!();
-                    }
-                    let value = record_values.get("_value").unwrap();
-                    entry.insert(field, value.clone());
+        let mut rows = HashMap::<GenericMap, usize>::new();
+        let mut items: Vec<GenericMap> = vec![];
+        for record in records {
+            let record = record?;
+            let key: GenericMap = match pivot {
+                Pivot::Off => {
+                    items.push(record.values);
+                    continue;
+                }
+                Pivot::Auto if !record.values.contains_key("_field") => {
+                    items.push(record.values);
+                    continue;
+                }
+                Pivot::Auto => record
+                    .values
+                    .iter()
+                    .filter(|(k, _)| !ignored_keys.contains(k.as_str()))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+                Pivot::RowKey(columns) => columns
+                    .iter()
+                    .map(|c| match record.values.get(c) {
+                        Some(v) => Ok((c.clone(), v.clone())),
+                        None => Err(RequestError::Deserializing {
+                            text: format!(
+                                "record of table {} has no row key column `{}`",
+                                record.table, c
+                            ),
+                        }),
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+
+            let (field, value) = field_value(&record.values).ok_or_else(|| {
+                RequestError::Deserializing {
+                    text: format!(
+                        "record of table {} can't be pivoted, it needs a string `_field` and a `_value` column",
+                        record.table
+                    ),
+                }
+            })?;
+            match rows.get(&key) {
+                Some(&index) => {
+                    items[index].insert(field, value);
                 }
                 None => {
-                    // Set field value
-                    let field;
-                    if let Value::String(f) = record_values.get("_field").unwrap() {
-                        field = f.clone();
-                    } else {
-                        unreachable!();
-                    }
-                    let value = record_values.get("_value").unwrap();
-                    record_values.insert(field, value.clone());
-
-                    build_table.insert(key.clone(), record_values);
-                    key_order.push(key);
+                    let mut row = record.values;
+                    row.insert(field, value);
+                    rows.insert(key, items.len());
+                    items.push(row);
                 }
             }
         }
 
-        // Build items based on the order the `key` is inserted
-        let mut items = vec![];
-        for key in key_order {
-            let entry = build_table.get(&key).unwrap();
-            items.push(entry.clone());
-        }
-
         Ok(Self { items })
     }
 }
 
-fn parse_value(s: &str, t: DataType, name: &str) -> Result<Value, RequestError> {
+fn csv_error(e: csv::Error) -> RequestError {
+    let text = e.to_string();
+    match e.into_kind() {
+        csv::ErrorKind::Io(source) => body_error(source),
+        _ => RequestError::Deserializing { text },
+    }
+}
+
+// The `_field` name and `_value` of a record
+fn field_value(values: &GenericMap) -> Option<(String, Value)> {
+    match (values.get("_field"), values.get("_value")) {
+        (Some(Value::String(field)), Some(value)) => Some((field.clone(), value.clone())),
+        _ => None,
+    }
+}
+
+// Parse a cell, the error describes why the text isn't a valid value.
+// An empty cell of a non-string column is a null value.
+fn parse_value(s: &str, t: DataType) -> Result<Value, String> {
+    if s.is_empty() && t != DataType::String {
+        return Ok(Value::Unknown);
+    }
     match t {
         DataType::String => Ok(Value::String(String::from(s))),
         DataType::Double => {
-            let v = s.parse::<f64>().unwrap();
+            let v = s.parse::<f64>().map_err(|e| e.to_string())?;
             Ok(Value::Double(OrderedFloat::from(v)))
         }
         DataType::Bool => {
-            if s.to_lowercase() == "false" {
+            if s.eq_ignore_ascii_case("true") {
+                Ok(Value::Bool(true))
+            } else if s.eq_ignore_ascii_case("false") {
                 Ok(Value::Bool(false))
             } else {
-                Ok(Value::Bool(true))
+                Err(String::from("expected true or false"))
             }
         }
         DataType::Long => {
-            let v = s.parse::<i64>().unwrap();
+            let v = s.parse::<i64>().map_err(|e| e.to_string())?;
             Ok(Value::Long(v))
         }
         DataType::UnsignedLong => {
-            let v = s.parse::<u64>().unwrap();
+            let v = s.parse::<u64>().map_err(|e| e.to_string())?;
             Ok(Value::UnsignedLong(v))
         }
-        DataType::Duration => match parse_duration(s) {
-            Ok(d) => Ok(Value::Duration(chrono::Duration::nanoseconds(d))),
-            Err(_) => Err(RequestError::Deserializing {
-                text: format!("invalid duration: {}, name: {}", s, name),
-            }),
-        },
+        DataType::Duration => {
+            let d = parse_duration(s).map_err(|_| String::from("invalid duration"))?;
+            Ok(Value::Duration(chrono::Duration::nanoseconds(d)))
+        }
         DataType::Base64Binary => {
-            let b = decode(s).unwrap();
+            let b = decode(s).map_err(|e| e.to_string())?;
             Ok(Value::Base64Binary(b))
         }
         DataType::TimeRFC => {
-            let t = DateTime::parse_from_rfc3339(s).unwrap();
+            let t = DateTime::parse_from_rfc3339(s).map_err(|e| e.to_string())?;
             Ok(Value::TimeRFC(t))
         }
     }
@@ -729,11 +1200,13 @@ fn parse_value(s: &str, t: DataType, name: &str) -> Result<Value, RequestError>
 mod tests {
     use super::*;
     use crate::FromDataPoint;
+    use crate::models::ast::{Position, Rule, Severity, SourceLocation};
     use mockito::{mock, Matcher};
 
     #[derive(Default, FromDataPoint)]
     struct Empty {}
 
+    #[test]
     fn query_suggestions() {
         let token = "some-token";
 
@@ -748,6 +1221,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn query_suggestions_name() {
         let token = "some-token";
         let suggestion_name = "some-name";
@@ -770,6 +1244,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn query() {
         let token = "some-token";
         let org = "some-org";
@@ -779,11 +1254,9 @@ mod tests {
             .match_header("Accepting-Encoding", "identity")
             .match_header("Content-Type", "application/json")
             .match_query(Matcher::UrlEncoded("org".into(), org.into()))
-            .match_body(
-                serde_json::to_string(&query.clone().unwrap_or_default())
-                    .unwrap()
-                    .as_str(),
-            )
+            .match_body(Matcher::JsonString(
+                serde_json::to_string(&query.clone().unwrap_or_default()).unwrap(),
+            ))
             .create();
 
         let client = Client::new(mockito::server_url(), org, token);
@@ -793,6 +1266,442 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
+    fn query_reports_missing_and_mistyped_keys() {
+        #[allow(dead_code)]
+        #[derive(Debug, FromDataPoint)]
+        struct Test {
+            a: String,
+            f: f64,
+            missing: String,
+        }
+
+        #[allow(dead_code)]
+        #[derive(Debug, FromDataPoint)]
+        struct Mistyped {
+            a: i64,
+        }
+
+        let text = "#datatype,string,long,dateTime:RFC3339,dateTime:RFC3339,dateTime:RFC3339,double,string,string,string
+#group,false,false,true,true,false,false,true,true,true
+#default,_result,,,,,,,,
+,result,table,_start,_stop,_time,_value,_field,_measurement,a
+,,0,2020-02-17T22:19:49.747562847Z,2020-02-18T22:19:49.747562847Z,2020-02-18T10:34:08.135814545Z,1.5,f,test,1
+";
+        let _mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .expect(2)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let err = client.query::<Test>(None).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                RequestError::RecordMapping {
+                    source: influxdb2_structmap::FromMapError::MissingKey { key }
+                } if key == "missing"
+            ),
+            "{}",
+            err
+        );
+
+        let err = client.query::<Mistyped>(None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Unable to convert query result: Expected i64 for key `a`, found String("1")"#
+        );
+    }
+
+    #[test]
+    fn query_with_field_attributes() {
+        #[derive(FromDataPoint)]
+        struct Test {
+            #[influxdb(rename = "_measurement")]
+            measurement: std::string::String,
+            a: Option<String>,
+            missing: Option<f64>,
+            #[influxdb(default)]
+            missing_with_default: i64,
+            #[influxdb(skip)]
+            skipped: Vec<u8>,
+            #[influxdb(rename = "_time")]
+            time: chrono::DateTime<chrono::Utc>,
+            r#f: f64,
+        }
+
+        let text = "#datatype,string,long,dateTime:RFC3339,dateTime:RFC3339,dateTime:RFC3339,double,string,string,string
+#group,false,false,true,true,false,false,true,true,true
+#default,_result,,,,,,,,
+,result,table,_start,_stop,_time,_value,_field,_measurement,a
+,,0,2020-02-17T22:19:49.747562847Z,2020-02-18T22:19:49.747562847Z,2020-02-18T10:34:08.135814545Z,1.5,f,test,1
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let result = client.query::<Test>(None).unwrap();
+
+        mock_server.assert();
+        assert_eq!(result.len(), 1);
+        let row = &result[0];
+        assert_eq!(row.measurement, "test");
+        assert_eq!(row.a.as_deref(), Some("1"));
+        assert_eq!(row.missing, None);
+        assert_eq!(row.missing_with_default, 0);
+        assert!(row.skipped.is_empty());
+        assert_eq!(row.time.to_rfc3339(), "2020-02-18T10:34:08.135814545+00:00");
+        assert_eq!(row.r#f, 1.5);
+    }
+
+    #[test]
+    fn query_as() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Test {
+            #[serde(rename = "_measurement")]
+            measurement: String,
+            a: u8,
+            f: f32,
+            missing: Option<String>,
+            #[serde(rename = "_time")]
+            time: chrono::DateTime<chrono::FixedOffset>,
+        }
+
+        let text = "#datatype,string,long,dateTime:RFC3339,dateTime:RFC3339,dateTime:RFC3339,double,string,string,string
+#group,false,false,true,true,false,false,true,true,true
+#default,_result,,,,,,,,
+,result,table,_start,_stop,_time,_value,_field,_measurement,a
+,,0,2020-02-17T22:19:49.747562847Z,2020-02-18T22:19:49.747562847Z,2020-02-18T10:34:08.135814545Z,1.5,f,test,1
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let result = client.query_as::<Test>(None).unwrap();
+
+        mock_server.assert();
+        assert_eq!(
+            result,
+            vec![Test {
+                measurement: "test".to_string(),
+                a: 1,
+                f: 1.5,
+                missing: None,
+                time: chrono::DateTime::parse_from_rfc3339("2020-02-18T10:34:08.135814545Z").unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn query_stream() {
+        #[derive(FromDataPoint)]
+        struct Test {
+            #[influxdb(rename = "_field")]
+            field: String,
+            f: Option<f64>,
+            i: Option<i64>,
+        }
+
+        let text = "#datatype,string,long,string,double
+#group,false,false,true,false
+#default,_result,,,
+,result,table,_field,_value
+,,0,f,1.5
+,,0,f,2.5
+
+#datatype,string,long,string,long
+#group,false,false,true,false
+#default,_result,,,
+,result,table,_field,_value
+,,1,i,3
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .expect(2)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let rows: Vec<Test> = client.query_stream(None).unwrap().collect().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!((rows[0].field.as_str(), rows[0].f, rows[0].i), ("f", Some(1.5), None));
+        assert_eq!((rows[1].field.as_str(), rows[1].f, rows[1].i), ("f", Some(2.5), None));
+        assert_eq!((rows[2].field.as_str(), rows[2].f, rows[2].i), ("i", None, Some(3)));
+
+        // Stop after the first record
+        let mut records = client.query_raw_stream(None).unwrap();
+        let first = records.next().unwrap().unwrap();
+        assert_eq!(first.values["_value"], Value::Double(OrderedFloat::from(1.5)));
+        drop(records);
+
+        mock_server.assert();
+    }
+
+    #[test]
+    fn query_tables() {
+        let text = "#datatype,string,long,dateTime:RFC3339,double,string,string,string
+#group,false,false,false,false,true,true,true
+#default,_result,,,,,,
+,result,table,_time,_value,_field,_measurement,host
+,,0,2020-02-18T10:34:08.135814545Z,1.5,usage,cpu,a
+,,0,2020-02-18T10:35:08.135814545Z,2.5,usage,cpu,a
+,,1,2020-02-18T10:34:08.135814545Z,3.5,usage,cpu,b
+
+#datatype,string,long,dateTime:RFC3339,long,string,string,string
+#group,false,false,false,false,true,true,true
+#default,_result,,,,,,
+,result,table,_time,_value,_field,_measurement,host
+,,2,2020-02-18T10:34:08.135814545Z,4,cores,cpu,a
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let tables = client.query_tables(None).unwrap();
+        mock_server.assert();
+
+        assert_eq!(tables.len(), 3);
+        assert_eq!(
+            tables.iter().map(|t| t.position).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            tables.iter().map(|t| t.records.len()).collect::<Vec<_>>(),
+            vec![2, 1, 1]
+        );
+
+        let value = &tables[0].columns[3];
+        assert_eq!(value.name, "_value");
+        assert_eq!(value.data_type, DataType::Double);
+        assert!(!value.group);
+        assert_eq!(tables[2].columns[3].data_type, DataType::Long);
+        assert_eq!(
+            tables[0]
+                .group_key_columns()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["_field", "_measurement", "host"]
+        );
+        assert_eq!(
+            tables[1].group_key().get("host"),
+            Some(&Value::String("b".into()))
+        );
+
+        let record = &tables[1].records[0];
+        assert_eq!(record.table, 1);
+        assert_eq!(record.field(), Some("usage"));
+        assert_eq!(record.measurement(), Some("cpu"));
+        assert_eq!(record.value(), Some(&Value::Double(OrderedFloat::from(3.5))));
+        assert_eq!(
+            record.time().unwrap().to_rfc3339(),
+            "2020-02-18T10:34:08.135814545+00:00"
+        );
+        assert_eq!(record.start(), None);
+        assert_eq!(tables[2].records[0].value(), Some(&Value::Long(4)));
+    }
+
+    #[test]
+    fn query_results() {
+        #[derive(FromDataPoint)]
+        struct Mean {
+            host: String,
+            usage: f64,
+        }
+
+        #[derive(FromDataPoint)]
+        struct Max {
+            #[influxdb(rename = "_value")]
+            value: i64,
+        }
+
+        let text = "#datatype,string,long,string,string,double
+#group,false,false,true,true,false
+#default,mean,,,,
+,result,table,host,_field,_value
+,,0,a,usage,1.5
+,,1,b,usage,2.5
+
+#datatype,string,long,string,string,long
+#group,false,false,true,true,false
+#default,max,,,,
+,result,table,host,_field,_value
+,,0,a,usage,3
+";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let results = client.query_results(None).unwrap();
+        mock_server.assert();
+
+        assert_eq!(results.len(), 2);
+        let mean: Vec<Mean> = rows(&results["mean"]).unwrap();
+        assert_eq!(mean.len(), 2);
+        assert_eq!((mean[0].host.as_str(), mean[0].usage), ("a", 1.5));
+        assert_eq!((mean[1].host.as_str(), mean[1].usage), ("b", 2.5));
+        let max: Vec<Max> = rows(&results["max"]).unwrap();
+        assert_eq!(max.len(), 1);
+        assert_eq!(max[0].value, 3);
+    }
+
+    #[test]
+    fn query_with_pivot() {
+        #[derive(Debug, FromDataPoint, PartialEq)]
+        struct Row {
+            host: String,
+            a: Option<i64>,
+            b: Option<i64>,
+            #[influxdb(rename = "_field")]
+            field: Option<String>,
+        }
+
+        let long = "#datatype,string,long,long,string,string,string,long
+#group,false,false,true,true,true,true,false
+#default,_result,,,,,,
+,result,table,_start,_field,host,region,_value
+,,0,1,a,x,eu,1
+,,1,2,b,x,us,2
+";
+        let wide = "#datatype,string,long,string,long,long
+#group,false,false,true,false,false
+#default,_result,,,,
+,result,table,host,a,b
+,,0,x,1,2
+";
+        let long_mock = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .match_body(Matcher::PartialJsonString(r#"{"query":"long"}"#.into()))
+            .with_body(long)
+            .expect(4)
+            .create();
+        let wide_mock = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .match_body(Matcher::PartialJsonString(r#"{"query":"wide"}"#.into()))
+            .with_body(wide)
+            .expect(2)
+            .create();
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let long = || Some(Query::new("long".into()));
+        let wide = || Some(Query::new("wide".into()));
+
+        // `region` differs, so the records are separate points
+        let rows: Vec<Row> = client.query_with_pivot(long(), &Pivot::Auto).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let key = Pivot::RowKey(vec!["host".into()]);
+        let rows: Vec<Row> = client.query_with_pivot(long(), &key).unwrap();
+        assert_eq!(
+            rows,
+            vec![Row {
+                host: "x".into(),
+                a: Some(1),
+                b: Some(2),
+                field: Some("a".into()),
+            }]
+        );
+
+        // A column missing from the records is an error, not an empty key
+        let typo = Pivot::RowKey(vec!["hots".into()]);
+        let err = client.query_with_pivot::<Row>(long(), &typo).unwrap_err();
+        assert!(
+            matches!(&err, RequestError::Deserializing { text } if text.contains("`hots`")),
+            "{}",
+            err
+        );
+
+        let rows: Vec<Row> = client.query_with_pivot(long(), &Pivot::Off).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].a, rows[0].b), (None, None));
+
+        // Wide records are kept as they are, unless a row key asks for a pivot
+        let rows: Vec<Row> = client.query_with_pivot(wide(), &Pivot::Auto).unwrap();
+        assert_eq!((rows[0].a, rows[0].b, rows[0].field.as_ref()), (Some(1), Some(2), None));
+        let err = client.query_with_pivot::<Row>(wide(), &key).unwrap_err();
+        assert!(
+            matches!(&err, RequestError::Deserializing { text } if text.contains("`_field`")),
+            "{}",
+            err
+        );
+
+        long_mock.assert();
+        wide_mock.assert();
+    }
+
+    #[test]
+    fn list_measurement_tag_values_uses_params() {
+        let text = "#datatype,string,long,string
+#group,false,false,false
+#default,_result,,
+,result,table,_value
+,,0,server01
+";
+        let expected = r#"{
+            "query": "import \"influxdata/influxdb/schema\"\n\n            schema.measurementTagValues(bucket: params.bucket, measurement: params.measurement, tag: params.tag, start: -1d)",
+            "params": {
+                "bucket": "my \"bucket\"",
+                "measurement": "cpu\") |> drop(",
+                "tag": "host"
+            }
+        }"#;
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .match_body(Matcher::PartialJsonString(expected.into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let values = client
+            .list_measurement_tag_values(
+                r#"my "bucket""#,
+                r#"cpu") |> drop("#,
+                "host",
+                Some("-1d"),
+                None,
+            )
+            .unwrap();
+
+        mock_server.assert();
+        assert_eq!(values, vec!["server01"]);
+    }
+
+    #[test]
+    fn query_params_are_typed() {
+        let time = DateTime::parse_from_rfc3339("2021-01-01T01:00:00+01:00").unwrap();
+        let query = Query::new("params.a".into())
+            .with_param("string", "a")
+            .with_param("int", -1)
+            .with_param("uint", 1u64)
+            .with_param("float", 0.5)
+            .with_param("bool", true)
+            .with_param("time", time)
+            .with_param("duration", chrono::Duration::minutes(-90));
+        let json = serde_json::to_value(&query).unwrap();
+        assert_eq!(
+            json["params"],
+            serde_json::json!({
+                "string": "a",
+                "int": -1,
+                "uint": 1,
+                "float": 0.5,
+                "bool": true,
+                "time": "2021-01-01T00:00:00Z",
+                "duration": "-1h30m",
+            })
+        );
+        let back: Query = serde_json::from_value(json).unwrap();
+        assert_eq!(back.params.unwrap()["int"], crate::models::ParamValue::Int(-1));
+    }
+
+    #[test]
     fn query_opt() {
         let token = "some-token";
         let org = "some-org";
@@ -802,7 +1711,9 @@ mod tests {
             .match_header("Accepting-Encoding", "identity")
             .match_header("Content-Type", "application/json")
             .match_query(Matcher::UrlEncoded("org".into(), org.into()))
-            .match_body(serde_json::to_string(&Query::default()).unwrap().as_str())
+            .match_body(Matcher::JsonString(
+                serde_json::to_string(&Query::default()).unwrap(),
+            ))
             .create();
 
         let client = Client::new(mockito::server_url(), org, token);
@@ -812,17 +1723,16 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn query_analyze() {
         let token = "some-token";
         let query: Option<Query> = Some(Query::new("some-influx-query-string".to_string()));
         let mock_server = mock("POST", "/api/v2/query/analyze")
             .match_header("Authorization", format!("Token {}", token).as_str())
             .match_header("Content-Type", "application/json")
-            .match_body(
-                serde_json::to_string(&query.clone().unwrap_or_default())
-                    .unwrap()
-                    .as_str(),
-            )
+            .match_body(Matcher::JsonString(
+                serde_json::to_string(&query.clone().unwrap_or_default()).unwrap(),
+            ))
             .create();
 
         let client = Client::new(mockito::server_url(), "org", token);
@@ -832,17 +1742,16 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn query_analyze_opt() {
         let token = "some-token";
         let query: Option<Query> = None;
         let mock_server = mock("POST", "/api/v2/query/analyze")
             .match_header("Authorization", format!("Token {}", token).as_str())
             .match_header("Content-Type", "application/json")
-            .match_body(
-                serde_json::to_string(&query.clone().unwrap_or_default())
-                    .unwrap()
-                    .as_str(),
-            )
+            .match_body(Matcher::JsonString(
+                serde_json::to_string(&query.clone().unwrap_or_default()).unwrap(),
+            ))
             .create();
 
         let client = Client::new(mockito::server_url(), "org", token);
@@ -852,6 +1761,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn query_ast() {
         let token = "some-token";
         let language_request: Option<LanguageRequest> =
@@ -859,11 +1769,9 @@ mod tests {
         let mock_server = mock("POST", "/api/v2/query/ast")
             .match_header("Authorization", format!("Token {}", token).as_str())
             .match_header("Content-Type", "application/json")
-            .match_body(
-                serde_json::to_string(&language_request.clone().unwrap_or_default())
-                    .unwrap()
-                    .as_str(),
-            )
+            .match_body(Matcher::JsonString(
+                serde_json::to_string(&language_request.clone().unwrap_or_default()).unwrap(),
+            ))
             .create();
 
         let client = Client::new(mockito::server_url(), "org", token);
@@ -873,17 +1781,16 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn query_ast_opt() {
         let token = "some-token";
         let language_request: Option<LanguageRequest> = None;
         let mock_server = mock("POST", "/api/v2/query/ast")
             .match_header("Authorization", format!("Token {}", token).as_str())
             .match_header("Content-Type", "application/json")
-            .match_body(
-                serde_json::to_string(&language_request.clone().unwrap_or_default())
-                    .unwrap()
-                    .as_str(),
-            )
+            .match_body(Matcher::JsonString(
+                serde_json::to_string(&language_request.clone().unwrap_or_default()).unwrap(),
+            ))
             .create();
 
         let client = Client::new(mockito::server_url(), "org", token);
@@ -893,6 +1800,259 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
+    fn lint_query_reports_analysis_errors() {
+        let analyze = mock("POST", "/api/v2/query/analyze")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"query": "from(bucket: \"a\" |> yield()"}"#.to_string(),
+            ))
+            .with_body(
+                r#"{"errors": [{"line": 1, "column": 17, "character": 16, "message": "expected RPAREN"}]}"#,
+            )
+            .create();
+
+        let client = Client::new(mockito::server_url(), "org", "some-token");
+        let query = Query::new(r#"from(bucket: "a" |> yield()"#.to_string());
+        let diagnostics = client.lint_query(&query).unwrap();
+        analyze.assert();
+
+        assert_eq!(
+            diagnostics,
+            [Diagnostic {
+                severity: Severity::Error,
+                rule: Rule::Analysis,
+                message: "expected RPAREN".to_string(),
+                location: Some(SourceLocation::at(Position::new(1, 17))),
+            }]
+        );
+    }
+
+    #[test]
+    fn lint_query_checks_script() {
+        let script = r#"from(bucket: "a")
+    |> yield()
+from(bucket: "b")
+    |> range(start: -1h)
+    |> filter(fn: (r) => true)
+    |> yield()"#;
+
+        fn ident(name: &str) -> serde_json::Value {
+            serde_json::json!({"type": "Identifier", "name": name})
+        }
+        fn call(name: &str, line: u32, column: u32, args: serde_json::Value) -> serde_json::Value {
+            let end = column + name.len() as u32 + 2;
+            serde_json::json!({
+                "type": "CallExpression",
+                "location": {"start": {"line": line, "column": column}, "end": {"line": line, "column": end}},
+                "callee": ident(name),
+                "arguments": [{"type": "ObjectExpression", "properties": args}],
+            })
+        }
+        fn pipe(argument: serde_json::Value, call: serde_json::Value) -> serde_json::Value {
+            serde_json::json!({"type": "PipeExpression", "argument": argument, "call": call})
+        }
+        fn bucket(name: &str) -> serde_json::Value {
+            serde_json::json!([{
+                "type": "Property",
+                "key": ident("bucket"),
+                "value": {"type": "StringLiteral", "value": name},
+            }])
+        }
+
+        let first = pipe(call("from", 1, 1, bucket("a")), call("yield", 2, 8, serde_json::json!([])));
+        let second = pipe(
+            pipe(
+                pipe(call("from", 3, 1, bucket("b")), call("range", 4, 8, serde_json::json!([]))),
+                call("filter", 5, 8, serde_json::json!([])),
+            ),
+            call("yield", 6, 8, serde_json::json!([])),
+        );
+        let ast = serde_json::json!({"ast": {"type": "Package", "package": "main", "files": [{
+            "type": "File",
+            "body": [
+                {"type": "ExpressionStatement", "expression": first},
+                {"type": "ExpressionStatement", "expression": second},
+            ],
+        }]}});
+
+        let analyze = mock("POST", "/api/v2/query/analyze")
+            .match_body(Matcher::PartialJsonString(
+                serde_json::json!({ "query": script }).to_string(),
+            ))
+            .with_body(r#"{"errors": []}"#)
+            .create();
+        let parse = mock("POST", "/api/v2/query/ast")
+            .match_body(Matcher::JsonString(
+                serde_json::json!({ "query": script }).to_string(),
+            ))
+            .with_body(ast.to_string())
+            .create();
+
+        let client = Client::new(mockito::server_url(), "org", "some-token");
+        let diagnostics = client.lint_query(&Query::new(script.to_string())).unwrap();
+        analyze.assert();
+        parse.assert();
+
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.rule, d.location.unwrap().start))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Severity::Warning, Rule::UnboundedRange, Position::new(1, 1)),
+                (Severity::Info, Rule::MissingFilter, Position::new(1, 1)),
+                (Severity::Error, Rule::DuplicateYield, Position::new(6, 8)),
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].message,
+            "yield name `_result` is already used on line 2"
+        );
+    }
+
+    fn parse_records(text: &str) -> Result<Vec<FluxRecord>, RequestError> {
+        QueryTableResult::with_format(text.as_bytes(), CsvFormat::of(None).unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_special_and_missing_values() {
+        let text = "#datatype,string,long,double,double,long,boolean,someNewType
+#group,false,false,false,false,false,false,false
+#default,_result,,,,,,
+,result,table,a,b,c,d,e
+,,0,+Inf,NaN,,TRUE,x
+";
+        let records = parse_records(text).unwrap();
+        let values = &records[0].values;
+        assert_eq!(values["a"], Value::Double(OrderedFloat::from(f64::INFINITY)));
+        assert!(matches!(values["b"], Value::Double(v) if v.is_nan()));
+        assert_eq!(values["c"], Value::Unknown);
+        assert_eq!(values["d"], Value::Bool(true));
+        assert_eq!(values["e"], Value::String("x".into()));
+    }
+
+    #[test]
+    fn parse_errors_name_the_value() {
+        let header = "#datatype,string,long,double,dateTime:RFC3339
+#group,false,false,false,false
+#default,_result,,,
+,result,table,_value,_time
+";
+        let cases = [
+            (
+                ",,0,abc,2020-02-18T10:34:08Z\n",
+                "invalid Double value `abc` in column `_value` on line 5",
+            ),
+            (
+                ",,0,1.5,yesterday\n",
+                "invalid TimeRFC value `yesterday` in column `_time` on line 5",
+            ),
+            (
+                ",,0,1.5,2020-02-18T10:34:08Z\n,,0,1.5",
+                "row has different number of columns than the table on line 6: 3 vs 4",
+            ),
+        ];
+        for (rows, expected) in &cases {
+            let err = parse_records(&format!("{}{}", header, rows)).unwrap_err();
+            assert!(
+                matches!(&err, RequestError::Deserializing { text } if text.starts_with(expected)),
+                "{}",
+                err
+            );
+        }
+    }
+
+    fn parse_with_dialect(text: &str, dialect: Dialect) -> Result<Vec<FluxRecord>, RequestError> {
+        let format = CsvFormat::new(Some(&dialect))?;
+        QueryTableResult::with_format(text.as_bytes(), format).collect()
+    }
+
+    #[test]
+    fn parse_custom_delimiter_without_group_and_default() {
+        let text = "#datatype;string;long;string;string
+;result;table;_field;_value
+;_result;0;usage;1,5
+";
+        let dialect = Dialect {
+            delimiter: Some(";".into()),
+            annotations: vec![Annotations::Datatype],
+            ..Default::default()
+        };
+        let records = parse_with_dialect(text, dialect).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].values["table"], Value::Long(0));
+        assert_eq!(records[0].values["_value"], Value::String("1,5".into()));
+
+        let dialect = Dialect {
+            delimiter: Some(";;".into()),
+            ..Default::default()
+        };
+        assert!(parse_with_dialect(text, dialect).is_err());
+    }
+
+    #[test]
+    fn parse_without_annotations() {
+        let text = ",result,table,_field,_value
+,_result,0,usage,1.5
+,_result,1,usage,2.5
+
+,result,table,_field,_value,host
+,_result,2,count,7,a
+";
+        let records = parse_with_dialect(text, Dialect::default()).unwrap();
+        assert_eq!(
+            records.iter().map(|r| r.table).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(records[0].values["_value"], Value::String("1.5".into()));
+        assert_eq!(records[2].values["host"], Value::String("a".into()));
+
+        let error = ",error,reference
+,query failed,897
+";
+        let err = parse_with_dialect(error, Dialect::default()).unwrap_err();
+        assert!(matches!(&err, RequestError::Deserializing { text } if text == "query failed,897"));
+    }
+
+    #[test]
+    fn parse_without_header() {
+        let text = "#datatype,string,long,double
+#comment,ignored
+,_result,0,1.5
+,_result,0,2.5
+";
+        let dialect = Dialect {
+            header: Some(false),
+            annotations: vec![Annotations::Datatype],
+            comment_prefix: Some("#".into()),
+            ..Default::default()
+        };
+        let records = parse_with_dialect(text, dialect).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].values["1"], Value::Long(0));
+        assert_eq!(records[1].values["2"], Value::Double(OrderedFloat::from(2.5)));
+    }
+
+    #[test]
+    fn query_csv_raw() {
+        let text = ",result,table,_value\n,_result,0,1\n";
+        let mock_server = mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "some-org".into()))
+            .with_body(text)
+            .create();
+
+        let client = Client::new(mockito::server_url(), "some-org", "some-token");
+        let mut body = String::new();
+        client
+            .query_csv_raw(None)
+            .unwrap()
+            .read_to_string(&mut body)
+            .unwrap();
+        mock_server.assert();
+        assert_eq!(body, text);
+    }
+
     #[test]
     fn test_query_table_result() {
         let text = "#datatype,string,long,dateTime:RFC3339,dateTime:RFC3339,dateTime:RFC3339,double,string,string,string,string
@@ -902,7 +2062,7 @@ mod tests {
 ,,0,2020-02-17T22:19:49.747562847Z,2020-02-18T22:19:49.747562847Z,2020-02-18T10:34:08.135814545Z,1.4,f,test,1,adsfasdf
 ,,0,2020-02-17T22:19:49.747562847Z,2020-02-18T22:19:49.747562847Z,2020-02-18T22:08:44.850214724Z,6.6,f,test,1,adsfasdf
 ";
-        let qtr = QueryTableResult::new(text);
+        let qtr = QueryTableResult::with_format(text.as_bytes(), CsvFormat::of(None).unwrap());
         #[rustfmt::skip]
         let expected: [FluxRecord; 2] = [
             FluxRecord {
@@ -910,9 +2070,9 @@ mod tests {
                 values: [
                     (String::from("result"), Value::String(String::from("_result"))),
                     (String::from("table"), Value::Long(0)),
-                    (String::from("_start"), parse_value("2020-02-17T22:19:49.747562847Z", DataType::TimeRFC, "_start").unwrap()),
-                    (String::from("_stop"), parse_value("2020-02-18T22:19:49.747562847Z", DataType::TimeRFC, "_stop").unwrap()),
-                    (String::from("_time"), parse_value("2020-02-18T10:34:08.135814545Z", DataType::TimeRFC, "_time").unwrap()),
+                    (String::from("_start"), parse_value("2020-02-17T22:19:49.747562847Z", DataType::TimeRFC).unwrap()),
+                    (String::from("_stop"), parse_value("2020-02-18T22:19:49.747562847Z", DataType::TimeRFC).unwrap()),
+                    (String::from("_time"), parse_value("2020-02-18T10:34:08.135814545Z", DataType::TimeRFC).unwrap()),
                     (String::from("_field"), Value::String(String::from("f"))),
                     (String::from("_measurement"), Value::String(String::from("test"))),
                     (String::from("_value"), Value::Double(OrderedFloat::from(1.4))),
@@ -925,9 +2085,9 @@ mod tests {
                 values: [
                     (String::from("result"), Value::String(String::from("_result"))),
                     (String::from("table"), Value::Long(0)),
-                    (String::from("_start"), parse_value("2020-02-17T22:19:49.747562847Z", DataType::TimeRFC, "_start").unwrap()),
-                    (String::from("_stop"), parse_value("2020-02-18T22:19:49.747562847Z", DataType::TimeRFC, "_stop").unwrap()),
-                    (String::from("_time"), parse_value("2020-02-18T22:08:44.850214724Z", DataType::TimeRFC, "_time").unwrap()),
+                    (String::from("_start"), parse_value("2020-02-17T22:19:49.747562847Z", DataType::TimeRFC).unwrap()),
+                    (String::from("_stop"), parse_value("2020-02-18T22:19:49.747562847Z", DataType::TimeRFC).unwrap()),
+                    (String::from("_time"), parse_value("2020-02-18T22:08:44.850214724Z", DataType::TimeRFC).unwrap()),
                     (String::from("_field"), Value::String(String::from("f"))),
                     (String::from("_measurement"), Value::String(String::from("test"))),
                     (String::from("_value"), Value::Double(OrderedFloat::from(6.6))),
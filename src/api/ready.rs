@@ -31,6 +31,7 @@ mod tests {
     use super::*;
     use mockito::mock;
 
+    #[test]
     fn ready() {
         let mock_server = mock("GET", "/ready").create();
 
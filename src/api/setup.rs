@@ -109,8 +109,9 @@ impl Client {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockito::mock;
+    use mockito::{mock, Matcher};
 
+    #[test]
     fn is_onboarding_allowed() {
         let mock_server = mock("GET", "/api/v2/setup").create();
 
@@ -121,6 +122,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn onboarding() {
         let token = "some-token";
         let username = "some-user";
@@ -130,12 +132,10 @@ mod tests {
         let retention_period_hrs = 1;
 
         let mock_server = mock("POST", "/api/v2/setup")
-            .match_body(
-                format!(
-                    r#"{{"username":"{}","org":"{}","bucket":"{}","password":"{}","retentionPeriodHrs":{}}}"#,
-                    username, org, bucket, password, retention_period_hrs
-                ).as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"username":"{}","org":"{}","bucket":"{}","password":"{}","retentionPeriodHrs":{}}}"#,
+                username, org, bucket, password, retention_period_hrs
+            )))
             .create();
 
         let client = Client::new(mockito::server_url(), org, token);
@@ -154,6 +154,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn post_setup_user() {
         let token = "some-token";
         let username = "some-user";
@@ -164,12 +165,10 @@ mod tests {
 
         let mock_server = mock("POST", "/api/v2/setup/user")
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body(
-                format!(
-                    r#"{{"username":"{}","org":"{}","bucket":"{}","password":"{}","retentionPeriodHrs":{}}}"#,
-                    username, org, bucket, password, retention_period_hrs
-                ).as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"username":"{}","org":"{}","bucket":"{}","password":"{}","retentionPeriodHrs":{}}}"#,
+                username, org, bucket, password, retention_period_hrs
+            )))
             .create();
 
         let client = Client::new(mockito::server_url(), org, token);
@@ -188,19 +187,17 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn onboarding_opt() {
         let username = "some-user";
         let org = "some-org";
         let bucket = "some-bucket";
 
         let mock_server = mock("POST", "/api/v2/setup")
-            .match_body(
-                format!(
-                    r#"{{"username":"{}","org":"{}","bucket":"{}"}}"#,
-                    username, org, bucket,
-                )
-                .as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"username":"{}","org":"{}","bucket":"{}"}}"#,
+                username, org, bucket,
+            )))
             .create();
 
         let client = Client::new(mockito::server_url(), org, "");
@@ -212,6 +209,7 @@ mod tests {
         mock_server.assert();
     }
 
+    #[test]
     fn post_setup_user_opt() {
         let token = "some-token";
         let username = "some-user";
@@ -220,13 +218,10 @@ mod tests {
 
         let mock_server = mock("POST", "/api/v2/setup/user")
             .match_header("Authorization", format!("Token {}", token).as_str())
-            .match_body(
-                format!(
-                    r#"{{"username":"{}","org":"{}","bucket":"{}"}}"#,
-                    username, org, bucket,
-                )
-                .as_str(),
-            )
+            .match_body(Matcher::JsonString(format!(
+                r#"{{"username":"{}","org":"{}","bucket":"{}"}}"#,
+                username, org, bucket,
+            )))
             .create();
 
         let client = Client::new(mockito::server_url(), org, token);
@@ -1,11 +1,13 @@
 //! Write API
 
-use crate::models::WriteDataPoint;
-use crate::{BodyBuilding, Client, Http, RequestError, UreqProcessing};
+use crate::models::data_point::{WriteTagKey, WriteTagValue};
+use crate::models::{DataPoint, WriteDataPoint, WriteSchema};
+use crate::{BodyBuilding, Client, Http, RequestError, SchemaValidation, UreqProcessing};
 
 use bytes::BufMut;
 use snafu::ResultExt;
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
 use ureq::http::{HeaderName, HeaderValue, StatusCode};
 use ureq::{AsSendBody, Body};
 
@@ -13,6 +15,8 @@ impl Client {
     /// Write line protocol data to the specified organization and bucket.
     /// This method writes with default timestamp precision (nanoseconds).
     /// Use write_line_protocol_with_precision if you want to write with a different precision.
+    ///
+    /// Any default tags configured on the client are merged into each line.
     pub fn write_line_protocol(
         &self,
         org: &str,
@@ -40,6 +44,31 @@ impl Client {
     }
 
     fn write_line_protocol_with_precision_headers(
+        &self,
+        org: &str,
+        bucket: &str,
+        mut body: impl AsSendBody,
+        precision: TimestampPrecision,
+        headers: impl IntoIterator<Item = (HeaderName, HeaderValue)>,
+    ) -> Result<(), RequestError> {
+        if self.default_tags.is_empty() {
+            return self.post_line_protocol(org, bucket, body, precision, headers);
+        }
+
+        let mut raw = Vec::new();
+        body.as_body()
+            .into_reader()
+            .read_to_end(&mut raw)
+            .context(BodyBuilding)?;
+        let mut enriched = Vec::with_capacity(raw.len());
+        self.default_tags
+            .write_lines_to(&raw, &mut enriched)
+            .context(BodyBuilding)?;
+
+        self.post_line_protocol(org, bucket, enriched, precision, headers)
+    }
+
+    fn post_line_protocol(
         &self,
         org: &str,
         bucket: &str,
@@ -105,6 +134,23 @@ impl Client {
         self.write_line_protocol_with_precision(&self.org, bucket, body, timestamp_precision)
 
     }
+
+    /// Check every `DataPoint` against `schema` and, only if all of them
+    /// match, write them to the specified bucket.
+    ///
+    /// This method writes with default timestamp precision (nanoseconds).
+    pub fn write_validated(
+        &self,
+        bucket: &str,
+        body: impl IntoIterator<Item = DataPoint>,
+        schema: &WriteSchema,
+    ) -> Result<(), RequestError> {
+        let points = body.into_iter().collect::<Vec<_>>();
+        for point in &points {
+            schema.validate(point).context(SchemaValidation)?;
+        }
+        self.write(bucket, points)
+    }
 }
 
 /// Possible timestamp precisions.
@@ -129,14 +175,177 @@ impl TimestampPrecision {
             Self::Nanoseconds => "ns",
         }
     }
+
+    /// Convert nanoseconds since the epoch to this precision, rounding down.
+    pub(crate) fn convert_nanos(self, nanos: i128) -> i128 {
+        let nanos_per_unit = match self {
+            Self::Seconds => 1_000_000_000,
+            Self::Milliseconds => 1_000_000,
+            Self::Microseconds => 1_000,
+            Self::Nanoseconds => 1,
+        };
+        nanos.div_euclid(nanos_per_unit)
+    }
+}
+
+/// Tags merged into every line written through a `Client`.
+///
+/// Tags can apply to every measurement or only to a single one. When a key is
+/// set at several levels, the tag already present on the point wins, followed
+/// by the measurement-level tag and finally the global one.
+///
+/// These are usually configured through the `ClientBuilder`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DefaultTags {
+    global: BTreeMap<String, String>,
+    measurements: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl DefaultTags {
+    /// Create an empty set of default tags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a tag added to points of every measurement.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.global.insert(key.into(), value.into());
+    }
+
+    /// Sets a tag added only to points of `measurement`.
+    pub fn insert_for_measurement(
+        &mut self,
+        measurement: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) {
+        self.measurements
+            .entry(measurement.into())
+            .or_default()
+            .insert(key.into(), value.into());
+    }
+
+    /// Returns true if no default tags are configured.
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty() && self.measurements.values().all(BTreeMap::is_empty)
+    }
+
+    /// Copy the line protocol in `body` to `w`, adding the default tags
+    /// missing from each line. Blank lines and comments are copied as is.
+    pub(crate) fn write_lines_to<W>(&self, body: &[u8], mut w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let mut lines = body.split(|b| *b == b'\n').peekable();
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim_ascii_start();
+            if trimmed.is_empty() || trimmed.starts_with(b"#") {
+                w.write_all(line)?;
+            } else {
+                self.write_line_to(line, &mut w)?;
+            }
+            if lines.peek().is_some() {
+                w.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_line_to<W>(&self, line: &[u8], mut w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let series_end = find_unescaped(line, b' ').unwrap_or(line.len());
+        let (series, rest) = line.split_at(series_end);
+
+        let mut parts = split_unescaped(series, b',');
+        let measurement = parts.next().unwrap_or_default();
+
+        // Keep the tags sorted, the same as `DataPoint` does.
+        let mut tags: BTreeMap<String, Vec<u8>> = parts
+            .map(|pair| {
+                let key_end = find_unescaped(pair, b'=').unwrap_or(pair.len());
+                (unescape(&pair[..key_end]), pair.to_vec())
+            })
+            .collect();
+
+        let measurement_tags = self.measurements.get(&unescape(measurement));
+        for (k, v) in measurement_tags.into_iter().flatten().chain(&self.global) {
+            if tags.contains_key(k) {
+                continue;
+            }
+            let mut pair = Vec::new();
+            k.write_tag_key_to(&mut pair)?;
+            pair.push(b'=');
+            v.write_tag_value_to(&mut pair)?;
+            tags.insert(k.clone(), pair);
+        }
+
+        w.write_all(measurement)?;
+        for pair in tags.values() {
+            w.write_all(b",")?;
+            w.write_all(pair)?;
+        }
+        w.write_all(rest)
+    }
+}
+
+/// Position of the first occurrence of `delimiter` not preceded by a backslash.
+fn find_unescaped(s: &[u8], delimiter: u8) -> Option<usize> {
+    let mut escaped = false;
+    for (i, b) in s.iter().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b if *b == delimiter => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_unescaped(mut s: &[u8], delimiter: u8) -> impl Iterator<Item = &[u8]> {
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        match find_unescaped(s, delimiter) {
+            Some(i) => {
+                let (part, tail) = s.split_at(i);
+                s = &tail[1..];
+                Some(part)
+            }
+            None => {
+                done = true;
+                Some(s)
+            }
+        }
+    })
+}
+
+fn unescape(s: &[u8]) -> String {
+    let mut out = Vec::with_capacity(s.len());
+    let mut escaped = false;
+    for b in s {
+        if !escaped && *b == b'\\' {
+            escaped = true;
+        } else {
+            out.push(*b);
+            escaped = false;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::DataPoint;
+    use crate::lp::{self, TagSpec};
+    use crate::models::{FieldType, MeasurementWriteSchema};
     use mockito::mock;
 
+    #[test]
     fn writing_points() {
         let org = "some-org";
         let bucket = "some-bucket";
@@ -182,6 +391,49 @@ cpu,host=server01,region=us-west usage=0.87
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn writing_serialized_points() {
+        #[derive(serde::Serialize)]
+        struct Cpu {
+            host: &'static str,
+            usage: f64,
+        }
+
+        let org = "some-org";
+        let bucket = "some-bucket";
+        let token = "some-token";
+
+        let mock_server = mock(
+            "POST",
+            format!("/api/v2/write?bucket={}&org={}&precision=ns", bucket, org).as_str(),
+        )
+        .match_body(
+            "\
+cpu,host=server01 usage=0.5
+cpu,host=server02 usage=0.87
+",
+        )
+        .with_status(204)
+        .create();
+
+        let client = Client::new(mockito::server_url(), org, token);
+
+        let cpus = vec![
+            Cpu {
+                host: "server01",
+                usage: 0.5,
+            },
+            Cpu {
+                host: "server02",
+                usage: 0.87,
+            },
+        ];
+        let result = client.write(bucket, lp::points(cpus, "cpu", TagSpec::new().tag("host")));
+        mock_server.assert();
+        assert!(result.is_ok());
+    }
+
+    #[test]
     fn writing_points_with_precision() {
         let org = "some-org";
         let bucket = "some-bucket";
@@ -222,6 +474,7 @@ cpu,host=server01 usage=0.5 1671095854
         assert!(result.is_ok());
     }
 
+    #[test]
     fn status_code_correctly_interpreted() {
         let org = "org";
         let token = "token";
@@ -253,4 +506,135 @@ cpu,host=server01 usage=0.5 1671095854
             assert!(write_with_status(status).is_err());
         }
     }
+
+    #[test]
+    fn writing_points_with_default_tags() {
+        let org = "some-org";
+        let bucket = "some-bucket";
+        let token = "some-token";
+
+        let mock_server = mock(
+            "POST",
+            format!("/api/v2/write?bucket={}&org={}&precision=ns", bucket, org).as_str(),
+        )
+        .match_body(
+            "\
+cpu,host=server01,region=us-west,service=api usage=0.5
+cpu,host=server02,region=eu-central,service=api usage=0.87
+",
+        )
+        .with_status(204)
+        .create();
+
+        let client = crate::ClientBuilder::new(mockito::server_url(), org, token)
+            .default_tags([("host", "unknown"), ("region", "us-west")])
+            .default_tag("service", "api")
+            .build()
+            .unwrap();
+
+        let points = vec![
+            DataPoint::builder("cpu")
+                .tag("host", "server01")
+                .field("usage", 0.5)
+                .build()
+                .unwrap(),
+            DataPoint::builder("cpu")
+                .tag("host", "server02")
+                .tag("region", "eu-central")
+                .field("usage", 0.87)
+                .build()
+                .unwrap(),
+        ];
+
+        let result = client.write(bucket, points);
+        mock_server.assert();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn default_tags_from_env() {
+        std::env::set_var("INFLUXDB2_TEST_DEFAULT_TAG", "server01");
+        let client = crate::ClientBuilder::new("http://localhost:8086", "org", "token")
+            .default_tag_from_env("host", "INFLUXDB2_TEST_DEFAULT_TAG")
+            .build()
+            .unwrap();
+        let mut expected = DefaultTags::new();
+        expected.insert("host", "server01");
+        assert_eq!(client.default_tags, expected);
+
+        let result = crate::ClientBuilder::new("http://localhost:8086", "org", "token")
+            .default_tag_from_env("host", "INFLUXDB2_TEST_DEFAULT_TAG_MISSING")
+            .build();
+        assert!(matches!(
+            result,
+            Err(crate::BuildError::DefaultTagEnvMissing { .. })
+        ));
+    }
+
+    fn enrich(tags: &DefaultTags, body: &str) -> String {
+        let mut out = Vec::new();
+        tags.write_lines_to(body.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn default_tags_merge_into_raw_line_protocol() {
+        let mut tags = DefaultTags::new();
+        tags.insert("host", "my host");
+        tags.insert("dc", "a");
+        tags.insert_for_measurement("mem", "dc", "b");
+
+        assert_eq!(
+            enrich(&tags, "cpu usage=1 1\n"),
+            "cpu,dc=a,host=my\\ host usage=1 1\n"
+        );
+        // point tags win over measurement tags, which win over global ones
+        assert_eq!(
+            enrich(&tags, "mem,host=h1 used=2i\n"),
+            "mem,dc=b,host=h1 used=2i\n"
+        );
+        // escaped delimiters in the measurement and tags are preserved
+        assert_eq!(
+            enrich(&tags, r#"my\ cpu,tag\=key=v\,1 f="a b,c=d""#),
+            r#"my\ cpu,dc=a,host=my\ host,tag\=key=v\,1 f="a b,c=d""#
+        );
+        // comments and blank lines are copied as is
+        assert_eq!(
+            enrich(&tags, "# comment\n\ncpu usage=1"),
+            "# comment\n\ncpu,dc=a,host=my\\ host usage=1"
+        );
+    }
+
+    #[test]
+    fn write_validated_rejects_whole_batch() {
+        let org = "some-org";
+        let bucket = "some-bucket";
+
+        let mock_server = mock(
+            "POST",
+            format!("/api/v2/write?bucket={}&org={}&precision=ns", bucket, org).as_str(),
+        )
+        .with_status(204)
+        .expect(0)
+        .create();
+
+        let client = Client::new(mockito::server_url(), org, "some-token");
+        let schema = WriteSchema::new().measurement(
+            "cpu",
+            MeasurementWriteSchema::new().field("usage", FieldType::F64),
+        );
+        let points = vec![
+            DataPoint::builder("cpu").field("usage", 0.5).build().unwrap(),
+            DataPoint::builder("cpu").field("usage", "high").build().unwrap(),
+        ];
+
+        let result = client.write_validated(bucket, points, &schema);
+        mock_server.assert();
+        assert!(matches!(
+            result,
+            Err(RequestError::SchemaValidation {
+                source: crate::models::SchemaError::FieldTypeMismatch { .. }
+            })
+        ));
+    }
 }
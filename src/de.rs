@@ -0,0 +1,350 @@
+//! Query result deserialization with serde
+//!
+//! Deserializes a `GenericMap`, e.g. the values of a `FluxRecord`, into any
+//! type implementing `Deserialize`. Each column is a struct field.
+//!
+//! - a missing or empty column is `None`
+//! - columns such as `_time` and `_value` need `#[serde(rename = "_time")]`
+//! - integers and floats are converted to the requested width when they fit,
+//!   and parsed from tag values
+//! - dates are RFC 3339 strings, e.g. for `chrono::DateTime`, or nanoseconds
+//!   since the epoch when an integer is requested
+//! - durations are nanoseconds
+//! - enums are deserialized from their variant name
+//!
+//! # Example
+//!
+//! ```
+//! use influxdb2::de;
+//! use influxdb2_structmap::value::Value;
+//! use influxdb2_structmap::GenericMap;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Cpu {
+//!     host: String,
+//!     #[serde(rename = "_value")]
+//!     usage: f32,
+//!     region: Option<String>,
+//! }
+//!
+//! let mut map = GenericMap::new();
+//! map.insert("host".into(), Value::String("server01".into()));
+//! map.insert("_value".into(), Value::Double(0.5.into()));
+//!
+//! let cpu: Cpu = de::from_genericmap(map).unwrap();
+//! assert_eq!(cpu.usage, 0.5);
+//! assert_eq!(cpu.region, None);
+//! ```
+
+use influxdb2_structmap::value::Value;
+use influxdb2_structmap::GenericMap;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Unexpected, Visitor};
+use serde::forward_to_deserialize_any;
+use snafu::Snafu;
+use std::collections::btree_map;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::api::query::FluxRecord;
+
+/// Errors that occur while deserializing a query result
+// `Self` can't be used in the source of a Snafu error
+#[allow(clippy::use_self)]
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Returned by a `Deserialize` implementation, e.g. for a missing field
+    #[snafu(display("{}", message))]
+    Custom {
+        /// The error message
+        message: String,
+    },
+    /// The value of a column couldn't be deserialized
+    #[snafu(display("Invalid value for `{}`: {}", key, source))]
+    Column {
+        /// The column name
+        key: String,
+        /// Underlying error
+        source: Box<Error>,
+    },
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Deserialize a row of a query result.
+pub fn from_genericmap<T: DeserializeOwned>(map: GenericMap) -> Result<T, Error> {
+    T::deserialize(MapDeserializer { map })
+}
+
+/// Deserialize the values of a `FluxRecord`.
+pub fn from_record<T: DeserializeOwned>(record: FluxRecord) -> Result<T, Error> {
+    from_genericmap(record.values)
+}
+
+struct MapDeserializer {
+    map: GenericMap,
+}
+
+impl<'de> de::Deserializer<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(MapAccess {
+            entries: self.map.into_iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct MapAccess {
+    entries: btree_map::IntoIter<String, Value>,
+    value: Option<(String, Value)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                let res = seed.deserialize(key.as_str().into_deserializer())?;
+                self.value = Some((key, value));
+                Ok(Some(res))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("next_value_seed is called after next_key_seed");
+        seed.deserialize(ValueDeserializer { value })
+            .map_err(|source| Error::Column {
+                key,
+                source: Box::new(source),
+            })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+// Tag values are always strings, so they are parsed when a number or a
+// boolean is requested.
+fn parse<'de, T, V>(
+    value: &str,
+    visitor: V,
+    visit: fn(V, T) -> Result<V::Value, Error>,
+) -> Result<V::Value, Error>
+where
+    T: FromStr,
+    V: Visitor<'de>,
+{
+    match value.parse() {
+        Ok(parsed) => visit(visitor, parsed),
+        Err(_) => Err(de::Error::invalid_value(Unexpected::Str(value), &visitor)),
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.value {
+                    Value::String(v) => parse(&v, visitor, V::$visit),
+                    value => Self { value }.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+struct ValueDeserializer {
+    value: Value,
+}
+
+impl ValueDeserializer {
+    fn is_empty(&self) -> bool {
+        match &self.value {
+            Value::Unknown => true,
+            Value::String(s) => s.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Unknown => visitor.visit_unit(),
+            Value::String(v) => visitor.visit_string(v),
+            Value::Double(v) => visitor.visit_f64(v.into_inner()),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Long(v) => visitor.visit_i64(v),
+            Value::UnsignedLong(v) => visitor.visit_u64(v),
+            Value::Duration(v) => match v.num_nanoseconds() {
+                Some(nanos) => visitor.visit_i64(nanos),
+                None => Err(de::Error::custom("duration overflows nanoseconds")),
+            },
+            Value::Base64Binary(v) => visitor.visit_byte_buf(v),
+            Value::TimeRFC(v) => visitor.visit_string(v.to_rfc3339()),
+        }
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::TimeRFC(v) => match v.timestamp_nanos_opt() {
+                Some(nanos) => visitor.visit_i64(nanos),
+                None => Err(de::Error::custom("time overflows nanoseconds")),
+            },
+            Value::String(v) => parse(&v, visitor, V::visit_i64),
+            value => Self { value }.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            Value::String(v) => visitor.visit_enum(v.into_deserializer()),
+            value => Self { value }.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, FixedOffset};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Region {
+        UsWest,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Host(String);
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Cpu {
+        host: Host,
+        region: Region,
+        #[serde(rename = "_value")]
+        usage: f32,
+        cores: u8,
+        note: Option<String>,
+        missing: Option<i64>,
+        #[serde(rename = "_time")]
+        time: DateTime<FixedOffset>,
+        #[serde(rename = "_start")]
+        start: i64,
+    }
+
+    fn row() -> GenericMap {
+        let time = DateTime::parse_from_rfc3339("2022-12-15T09:17:34Z").unwrap();
+        let mut map = GenericMap::new();
+        map.insert("host".into(), Value::String("server01".into()));
+        map.insert("region".into(), Value::String("uswest".into()));
+        map.insert("_value".into(), Value::Double(0.5.into()));
+        map.insert("cores".into(), Value::String("4".into()));
+        map.insert("note".into(), Value::String("".into()));
+        map.insert("_time".into(), Value::TimeRFC(time));
+        map.insert("_start".into(), Value::TimeRFC(time));
+        map
+    }
+
+    #[test]
+    fn deserialize_row() {
+        let cpu: Cpu = from_genericmap(row()).unwrap();
+        assert_eq!(
+            cpu,
+            Cpu {
+                host: Host("server01".into()),
+                region: Region::UsWest,
+                usage: 0.5,
+                cores: 4,
+                note: None,
+                missing: None,
+                time: DateTime::parse_from_rfc3339("2022-12-15T09:17:34Z").unwrap(),
+                start: 1_671_095_854_000_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn errors_name_the_column() {
+        let mut map = row();
+        map.insert("cores".into(), Value::Long(1000));
+        let err = from_genericmap::<Cpu>(map).unwrap_err();
+        assert!(
+            matches!(&err, Error::Column { key, .. } if key == "cores"),
+            "{}",
+            err
+        );
+
+        let mut map = row();
+        map.remove("host");
+        let err = from_genericmap::<Cpu>(map).unwrap_err();
+        assert_eq!(err.to_string(), "missing field `host`");
+    }
+}
@@ -0,0 +1,377 @@
+//! Export of query results
+//!
+//! Writes the records of a query result, e.g. from
+//! `Client::query_raw_stream`, as plain CSV, as JSON Lines or back as line
+//! protocol. Records are written as they are read, so a result of any size
+//! can be exported.
+//!
+//! Values are written as text the way Flux writes them: times in RFC 3339,
+//! durations such as `1h30m` and binary data in base64. In JSON, numbers and
+//! booleans keep their type.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use fallible_iterator::FallibleIterator;
+//! use influxdb2::export;
+//! use influxdb2::models::Query;
+//! use influxdb2::Client;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = Client::new("http://localhost:8086", "org", "token");
+//! let query = Query::new(r#"from(bucket: "cpu") |> range(start: -1h)"#.to_string());
+//! let records = client.query_raw_stream(Some(query))?;
+//! let file = std::fs::File::create("cpu.lp")?;
+//! export::to_line_protocol(file, records.iterator(), &[])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::SecondsFormat;
+use influxdb2_structmap::value::Value;
+use snafu::{ResultExt, Snafu};
+use std::io;
+
+use crate::api::query::FluxRecord;
+use crate::flux::Literal;
+use crate::models::{DataPoint, FieldValue, WriteDataPoint};
+use crate::RequestError;
+
+/// Errors that occur while exporting a query result
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// A record couldn't be read from the query result
+    #[snafu(display("Unable to read the query result: {}", source))]
+    Reading {
+        /// Underlying error
+        source: RequestError,
+    },
+    /// The output couldn't be written
+    #[snafu(display("Unable to write the export: {}", source))]
+    Writing {
+        /// Underlying error
+        source: io::Error,
+    },
+    /// A CSV row couldn't be written
+    #[snafu(display("Unable to write CSV: {}", source))]
+    Csv {
+        /// Underlying error
+        source: csv::Error,
+    },
+    /// A JSON object couldn't be written
+    #[snafu(display("Unable to write JSON: {}", source))]
+    Json {
+        /// Underlying error
+        source: serde_json::Error,
+    },
+    /// A record has no string `_measurement` to write it as a point of
+    #[snafu(display("Record of table {} has no `_measurement`", table))]
+    MissingMeasurement {
+        /// The table of the record
+        table: i32,
+    },
+}
+
+/// Write `records` as CSV, with a header row naming the columns.
+///
+/// Records of different tables may have different columns. Whenever the
+/// columns change, an empty line and a new header row start another block,
+/// like tables do in the annotated CSV of InfluxDB. Null values are empty.
+pub fn to_csv<W, I>(writer: W, records: I) -> Result<(), Error>
+where
+    W: io::Write,
+    I: IntoIterator<Item = Result<FluxRecord, RequestError>>,
+{
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut header: Option<Vec<String>> = None;
+    for record in records {
+        let record = record.context(Reading)?;
+        if !header
+            .as_ref()
+            .is_some_and(|header| header.iter().eq(record.values.keys()))
+        {
+            if header.is_some() {
+                let mut inner = writer
+                    .into_inner()
+                    .map_err(|e| io::Error::new(e.error().kind(), e.to_string()))
+                    .context(Writing)?;
+                inner.write_all(b"\n").context(Writing)?;
+                writer = csv::Writer::from_writer(inner);
+            }
+            writer.write_record(record.values.keys()).context(Csv)?;
+            header = Some(record.values.keys().cloned().collect());
+        }
+        writer
+            .write_record(record.values.values().map(text))
+            .context(Csv)?;
+    }
+    writer.flush().context(Writing)
+}
+
+/// Write `records` as JSON Lines, one object per record.
+///
+/// Each column is a key of the object. Nulls and non-finite floats are
+/// `null`.
+pub fn to_json_lines<W, I>(mut writer: W, records: I) -> Result<(), Error>
+where
+    W: io::Write,
+    I: IntoIterator<Item = Result<FluxRecord, RequestError>>,
+{
+    for record in records {
+        let record = record.context(Reading)?;
+        let object: serde_json::Map<String, serde_json::Value> = record
+            .values
+            .iter()
+            .map(|(column, value)| (column.clone(), json(value)))
+            .collect();
+        serde_json::to_writer(&mut writer, &object).context(Json)?;
+        writer.write_all(b"\n").context(Writing)?;
+    }
+    writer.flush().context(Writing)
+}
+
+/// Write `records` as line protocol, one point per record.
+///
+/// The point is of the record's `_measurement`, at its `_time` if it has
+/// one. A record in long format, with a `_field` column, has its `_value` as
+/// the only field, and every other string column as a tag. In a record
+/// without `_field`, e.g. after a `pivot()`, the columns in `tag_columns`
+/// are tags and all the others are fields, strings included, so that the
+/// schema stays the same. `result`, `table`, `_start` and `_stop` are left
+/// out.
+///
+/// Empty tag values, which Flux uses for missing group columns, are left
+/// out. Durations and times are written as integer nanoseconds and binary
+/// data as a base64 string. Records without a non-null field are skipped.
+pub fn to_line_protocol<W, I>(mut writer: W, records: I, tag_columns: &[&str]) -> Result<(), Error>
+where
+    W: io::Write,
+    I: IntoIterator<Item = Result<FluxRecord, RequestError>>,
+{
+    for record in records {
+        let record = record.context(Reading)?;
+        if let Some(point) = data_point(&record, tag_columns)? {
+            point.write_data_point_to(&mut writer).context(Writing)?;
+        }
+    }
+    writer.flush().context(Writing)
+}
+
+fn data_point(record: &FluxRecord, tag_columns: &[&str]) -> Result<Option<DataPoint>, Error> {
+    let measurement = record.measurement().ok_or(Error::MissingMeasurement {
+        table: record.table,
+    })?;
+    let mut builder = DataPoint::builder(measurement);
+    if let (Some(field), Some(value)) = (record.field(), record.value()) {
+        if let Some(value) = field_value(value) {
+            builder = builder.field(field, value);
+        }
+    }
+    let long = record.field().is_some();
+    for (column, value) in &record.values {
+        match (column.as_str(), value) {
+            ("result" | "table" | "_start" | "_stop" | "_time" | "_measurement", _) => {}
+            ("_field" | "_value", _) if long => {}
+            (_, Value::String(v)) if long => {
+                if !v.is_empty() {
+                    builder = builder.tag(column.as_str(), v.as_str());
+                }
+            }
+            _ if long => {}
+            _ if tag_columns.contains(&column.as_str()) => {
+                let v = text(value);
+                if !v.is_empty() {
+                    builder = builder.tag(column.as_str(), v);
+                }
+            }
+            (_, value) => {
+                if let Some(value) = field_value(value) {
+                    builder = builder.field(column.as_str(), value);
+                }
+            }
+        }
+    }
+    if let Some(time) = record.time().and_then(|time| time.timestamp_nanos_opt()) {
+        builder = builder.timestamp(time);
+    }
+    Ok(builder.build().ok())
+}
+
+fn field_value(value: &Value) -> Option<FieldValue> {
+    match value {
+        Value::Unknown => None,
+        Value::String(v) => Some(FieldValue::String(v.clone())),
+        Value::Double(v) => Some(FieldValue::F64(v.into_inner())),
+        Value::Bool(v) => Some(FieldValue::Bool(*v)),
+        Value::Long(v) => Some(FieldValue::I64(*v)),
+        Value::UnsignedLong(v) => Some(FieldValue::U64(*v)),
+        Value::Duration(v) => v.num_nanoseconds().map(FieldValue::I64),
+        Value::Base64Binary(v) => Some(FieldValue::String(base64::encode(v))),
+        Value::TimeRFC(v) => v.timestamp_nanos_opt().map(FieldValue::I64),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Unknown => String::new(),
+        Value::String(v) => v.clone(),
+        Value::Double(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::Long(v) => v.to_string(),
+        Value::UnsignedLong(v) => v.to_string(),
+        Value::Duration(v) => Literal::Duration(*v).to_string(),
+        Value::Base64Binary(v) => base64::encode(v),
+        Value::TimeRFC(v) => v.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+    }
+}
+
+fn json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Unknown => serde_json::Value::Null,
+        Value::Double(v) => serde_json::Number::from_f64(v.into_inner())
+            .map_or(serde_json::Value::Null, serde_json::Value::Number),
+        Value::Bool(v) => (*v).into(),
+        Value::Long(v) => (*v).into(),
+        Value::UnsignedLong(v) => (*v).into(),
+        value => text(value).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use influxdb2_structmap::GenericMap;
+
+    fn record(table: i32, values: Vec<(&str, Value)>) -> Result<FluxRecord, RequestError> {
+        let values: GenericMap = values
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        Ok(FluxRecord { table, values })
+    }
+
+    fn records() -> Vec<Result<FluxRecord, RequestError>> {
+        let time = DateTime::parse_from_rfc3339("2021-01-01T00:00:00.5Z").unwrap();
+        vec![
+            record(
+                0,
+                vec![
+                    ("result", Value::String("_result".into())),
+                    ("table", Value::Long(0)),
+                    ("_start", Value::TimeRFC(time)),
+                    ("_time", Value::TimeRFC(time)),
+                    ("_measurement", Value::String("cpu".into())),
+                    ("host", Value::String("a b".into())),
+                    ("_field", Value::String("usage".into())),
+                    ("_value", Value::Double(1.5.into())),
+                ],
+            ),
+            record(
+                1,
+                vec![
+                    ("result", Value::String("_result".into())),
+                    ("table", Value::Long(1)),
+                    ("_start", Value::TimeRFC(time)),
+                    ("_time", Value::TimeRFC(time)),
+                    ("_measurement", Value::String("cpu".into())),
+                    ("host", Value::String("a b".into())),
+                    ("_field", Value::String("status".into())),
+                    ("_value", Value::String("ok".into())),
+                ],
+            ),
+            record(
+                2,
+                vec![
+                    ("_measurement", Value::String("mem".into())),
+                    ("host", Value::String("c".into())),
+                    ("free", Value::Long(7)),
+                    ("uptime", Value::Duration(chrono::Duration::seconds(90))),
+                    ("swap", Value::Unknown),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        to_csv(&mut out, records()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "_field,_measurement,_start,_time,_value,host,result,table
+usage,cpu,2021-01-01T00:00:00.500Z,2021-01-01T00:00:00.500Z,1.5,a b,_result,0
+status,cpu,2021-01-01T00:00:00.500Z,2021-01-01T00:00:00.500Z,ok,a b,_result,1
+
+_measurement,free,host,swap,uptime
+mem,7,c,,1m30s
+"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut out = Vec::new();
+        to_json_lines(&mut out, records().into_iter().skip(1)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"_field":"status","_measurement":"cpu","_start":"2021-01-01T00:00:00.500Z","_time":"2021-01-01T00:00:00.500Z","_value":"ok","host":"a b","result":"_result","table":1}
+{"_measurement":"mem","free":7,"host":"c","swap":null,"uptime":"1m30s"}
+"#
+        );
+    }
+
+    #[test]
+    fn line_protocol() {
+        let mut out = Vec::new();
+        to_line_protocol(&mut out, records(), &["host"]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"cpu,host=a\ b usage=1.5 1609459200500000000
+cpu,host=a\ b status="ok" 1609459200500000000
+mem,host=c free=7i,uptime=90000000000i
+"#
+        );
+    }
+
+    #[test]
+    fn line_protocol_keeps_pivoted_string_fields() {
+        let records = vec![
+            record(
+                0,
+                vec![
+                    ("_measurement", Value::String("cpu".into())),
+                    ("_field", Value::String("usage".into())),
+                    ("_value", Value::Double(0.5.into())),
+                    ("host", Value::String("a".into())),
+                    ("region", Value::String("".into())),
+                ],
+            ),
+            record(
+                1,
+                vec![
+                    ("_measurement", Value::String("cpu".into())),
+                    ("host", Value::String("a".into())),
+                    ("region", Value::String("".into())),
+                    ("status", Value::String("ok".into())),
+                    ("usage", Value::Double(0.5.into())),
+                ],
+            ),
+        ];
+        let mut out = Vec::new();
+        to_line_protocol(&mut out, records, &["host", "region"]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"cpu,host=a usage=0.5
+cpu,host=a status="ok",usage=0.5
+"#
+        );
+    }
+
+    #[test]
+    fn line_protocol_needs_a_measurement() {
+        let records = vec![record(3, vec![("_value", Value::Long(1))])];
+        let error = to_line_protocol(Vec::new(), records, &[]).unwrap_err();
+        assert!(matches!(error, Error::MissingMeasurement { table: 3 }));
+    }
+}
@@ -0,0 +1,718 @@
+//! Flux query builder
+//!
+//! Builds Flux queries without string formatting, so bucket names, tag
+//! values and patterns are always quoted and escaped correctly.
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::Duration;
+//! use influxdb2::flux::{self, Aggregate};
+//!
+//! let query = flux::from("telegraf")
+//!     .range_start(Duration::hours(-1))
+//!     .filter(flux::measurement().eq("cpu") & flux::tag("host").eq("server \"01\""))
+//!     .aggregate_window(Duration::minutes(5), Aggregate::Mean)
+//!     .yield_("mean");
+//!
+//! assert_eq!(
+//!     query.to_string(),
+//!     r#"from(bucket: "telegraf")
+//!   |> range(start: -1h)
+//!   |> filter(fn: (r) => r._measurement == "cpu" and r.host == "server \"01\"")
+//!   |> aggregateWindow(every: 5m, fn: mean)
+//!   |> yield(name: "mean")"#
+//! );
+//! ```
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not};
+
+use crate::models::Query;
+
+/// A Flux query, built from a `from()` source and the functions it is
+/// piped into
+#[derive(Clone, Debug, PartialEq)]
+pub struct FluxQuery {
+    source: String,
+    pipes: Vec<String>,
+}
+
+/// Start a query reading from a bucket.
+pub fn from(bucket: &str) -> FluxQuery {
+    FluxQuery {
+        source: format!("from(bucket: {})", Literal::from(bucket)),
+        pipes: Vec::new(),
+    }
+}
+
+impl FluxQuery {
+    fn pipe(mut self, call: String) -> Self {
+        self.pipes.push(call);
+        self
+    }
+
+    /// Keep records between `start` and `stop`.
+    pub fn range(self, start: impl Into<Literal>, stop: impl Into<Literal>) -> Self {
+        let call = format!("range(start: {}, stop: {})", start.into(), stop.into());
+        self.pipe(call)
+    }
+
+    /// Keep records from `start` until now.
+    pub fn range_start(self, start: impl Into<Literal>) -> Self {
+        let call = format!("range(start: {})", start.into());
+        self.pipe(call)
+    }
+
+    /// Keep records matching a predicate.
+    pub fn filter(self, predicate: Expr) -> Self {
+        let call = format!("filter(fn: (r) => {})", predicate);
+        self.pipe(call)
+    }
+
+    /// Aggregate the values of each window of `every`.
+    pub fn aggregate_window(self, every: chrono::Duration, aggregate: Aggregate) -> Self {
+        let call = format!(
+            "aggregateWindow(every: {}, fn: {})",
+            Literal::Duration(every),
+            aggregate
+        );
+        self.pipe(call)
+    }
+
+    /// Turn the values of `column_key` into columns, one row per
+    /// `row_key`.
+    pub fn pivot(self, row_key: &[&str], column_key: &[&str], value_column: &str) -> Self {
+        let call = format!(
+            "pivot(rowKey: {}, columnKey: {}, valueColumn: {})",
+            string_array(row_key),
+            string_array(column_key),
+            Literal::from(value_column)
+        );
+        self.pipe(call)
+    }
+
+    /// Pivot `_field` into columns holding `_value`, one row per `_time`.
+    pub fn pivot_fields(self) -> Self {
+        self.pivot(&["_time"], &["_field"], "_value")
+    }
+
+    /// Regroup the tables by the given columns.
+    pub fn group(self, columns: &[&str]) -> Self {
+        let call = format!("group(columns: {})", string_array(columns));
+        self.pipe(call)
+    }
+
+    /// Sort the records of each table by the given columns.
+    pub fn sort(self, columns: &[&str], desc: bool) -> Self {
+        let call = format!("sort(columns: {}, desc: {})", string_array(columns), desc);
+        self.pipe(call)
+    }
+
+    /// Keep at most `n` records of each table.
+    pub fn limit(self, n: u64) -> Self {
+        self.pipe(format!("limit(n: {})", n))
+    }
+
+    /// Keep only the given columns.
+    pub fn keep(self, columns: &[&str]) -> Self {
+        let call = format!("keep(columns: {})", string_array(columns));
+        self.pipe(call)
+    }
+
+    /// Remove the given columns.
+    pub fn drop(self, columns: &[&str]) -> Self {
+        let call = format!("drop(columns: {})", string_array(columns));
+        self.pipe(call)
+    }
+
+    /// Keep the first record of each table.
+    pub fn first(self) -> Self {
+        self.pipe(String::from("first()"))
+    }
+
+    /// Keep the last record of each table.
+    pub fn last(self) -> Self {
+        self.pipe(String::from("last()"))
+    }
+
+    /// Name the result of the query.
+    pub fn yield_(self, name: &str) -> Self {
+        let call = format!("yield(name: {})", Literal::from(name));
+        self.pipe(call)
+    }
+
+    /// The query to send to the server
+    pub fn to_query(&self) -> Query {
+        Query::new(self.to_string())
+    }
+}
+
+impl fmt::Display for FluxQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)?;
+        for pipe in &self.pipes {
+            write!(f, "\n  |> {}", pipe)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<FluxQuery> for Query {
+    fn from(query: FluxQuery) -> Self {
+        query.to_query()
+    }
+}
+
+/// Aggregate function of `aggregate_window`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    /// `mean`
+    Mean,
+    /// `median`
+    Median,
+    /// `min`
+    Min,
+    /// `max`
+    Max,
+    /// `sum`
+    Sum,
+    /// `count`
+    Count,
+    /// `first`
+    First,
+    /// `last`
+    Last,
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Mean => "mean",
+            Self::Median => "median",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Sum => "sum",
+            Self::Count => "count",
+            Self::First => "first",
+            Self::Last => "last",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A Flux literal
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    /// A string, e.g. `"cpu"`
+    String(String),
+    /// An integer
+    Int(i64),
+    /// An unsigned integer, written as `uint(v: ...)`
+    UInt(u64),
+    /// A float
+    Float(f64),
+    /// A boolean
+    Bool(bool),
+    /// A duration, e.g. `-1h30m`
+    Duration(chrono::Duration),
+    /// A point in time, e.g. `2021-01-01T00:00:00Z`
+    Time(DateTime<Utc>),
+    /// A regular expression, e.g. `/^cpu/`
+    Regex(String),
+    /// The `now()` of the query
+    Now,
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) => write_string(f, s),
+            Self::Int(v) => write!(f, "{}", v),
+            Self::UInt(v) => write!(f, "uint(v: {})", v),
+            Self::Float(v) if v.is_nan() => f.write_str(r#"float(v: "NaN")"#),
+            Self::Float(v) if v.is_infinite() => {
+                let sign = if *v > 0.0 { '+' } else { '-' };
+                write!(f, r#"float(v: "{}Inf")"#, sign)
+            }
+            Self::Float(v) => {
+                // A float literal needs a decimal point, `1` is an integer.
+                let s = v.to_string();
+                if s.contains('.') {
+                    f.write_str(&s)
+                } else {
+                    write!(f, "{}.0", s)
+                }
+            }
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Duration(d) => write_duration(f, *d),
+            Self::Time(t) => f.write_str(&t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            Self::Regex(r) => write!(f, "/{}/", r.replace('/', "\\/")),
+            Self::Now => f.write_str("now()"),
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    write_escaped(f, s)?;
+    f.write_str("\"")
+}
+
+/// Escapes the contents of a string literal
+pub(crate) fn write_escaped<W: fmt::Write>(w: &mut W, s: &str) -> fmt::Result {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            // `${` starts an interpolation
+            '$' if chars.peek() == Some(&'{') => w.write_str("\\$")?,
+            c => w.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_duration(f: &mut fmt::Formatter<'_>, d: chrono::Duration) -> fmt::Result {
+    const UNITS: [(&str, i128); 7] = [
+        ("d", 86_400_000_000_000),
+        ("h", 3_600_000_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("ms", 1_000_000),
+        ("us", 1_000),
+        ("ns", 1),
+    ];
+
+    // Nanoseconds overflow an i64 after about 292 years
+    let mut nanos = match d.num_nanoseconds() {
+        Some(nanos) => i128::from(nanos),
+        None => i128::from(d.num_microseconds().unwrap_or(i64::MAX)) * 1_000,
+    };
+    if nanos == 0 {
+        return f.write_str("0s");
+    }
+    if nanos < 0 {
+        f.write_str("-")?;
+        nanos = -nanos;
+    }
+    for (unit, size) in &UNITS {
+        if nanos >= *size {
+            write!(f, "{}{}", nanos / size, unit)?;
+            nanos %= size;
+        }
+    }
+    Ok(())
+}
+
+fn string_array(values: &[&str]) -> String {
+    let values: Vec<String> = values
+        .iter()
+        .map(|v| Literal::from(*v).to_string())
+        .collect();
+    format!("[{}]", values.join(", "))
+}
+
+impl From<&str> for Literal {
+    fn from(v: &str) -> Self {
+        Self::String(v.to_string())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(v: String) -> Self {
+        Self::String(v)
+    }
+}
+
+impl From<&String> for Literal {
+    fn from(v: &String) -> Self {
+        Self::String(v.clone())
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<i32> for Literal {
+    fn from(v: i32) -> Self {
+        Self::Int(v.into())
+    }
+}
+
+impl From<u64> for Literal {
+    fn from(v: u64) -> Self {
+        Self::UInt(v)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<chrono::Duration> for Literal {
+    fn from(v: chrono::Duration) -> Self {
+        Self::Duration(v)
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for Literal {
+    fn from(v: DateTime<Tz>) -> Self {
+        Self::Time(v.with_timezone(&Utc))
+    }
+}
+
+/// A column of the record `r` a predicate is applied to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    name: String,
+}
+
+/// A column by name, e.g. a tag or a pivoted field.
+pub fn col(name: &str) -> Column {
+    Column {
+        name: name.to_string(),
+    }
+}
+
+/// A tag column.
+pub fn tag(name: &str) -> Column {
+    col(name)
+}
+
+/// The `_measurement` column.
+pub fn measurement() -> Column {
+    col("_measurement")
+}
+
+/// The `_field` column.
+pub fn field() -> Column {
+    col("_field")
+}
+
+/// The `_value` column.
+pub fn value() -> Column {
+    col("_value")
+}
+
+/// The `_time` column.
+pub fn time() -> Column {
+    col("_time")
+}
+
+/// A regular expression literal, to use with `Column::matches`.
+pub fn regex(pattern: &str) -> Literal {
+    Literal::Regex(pattern.to_string())
+}
+
+impl Column {
+    fn compare(&self, operator: Operator, value: Literal) -> Expr {
+        Expr::Compare {
+            column: self.clone(),
+            operator,
+            value,
+        }
+    }
+
+    /// `r.column == value`
+    pub fn eq(&self, value: impl Into<Literal>) -> Expr {
+        self.compare(Operator::Eq, value.into())
+    }
+
+    /// `r.column != value`
+    pub fn ne(&self, value: impl Into<Literal>) -> Expr {
+        self.compare(Operator::Ne, value.into())
+    }
+
+    /// `r.column < value`
+    pub fn lt(&self, value: impl Into<Literal>) -> Expr {
+        self.compare(Operator::Lt, value.into())
+    }
+
+    /// `r.column <= value`
+    pub fn le(&self, value: impl Into<Literal>) -> Expr {
+        self.compare(Operator::Le, value.into())
+    }
+
+    /// `r.column > value`
+    pub fn gt(&self, value: impl Into<Literal>) -> Expr {
+        self.compare(Operator::Gt, value.into())
+    }
+
+    /// `r.column >= value`
+    pub fn ge(&self, value: impl Into<Literal>) -> Expr {
+        self.compare(Operator::Ge, value.into())
+    }
+
+    /// `r.column =~ /pattern/`
+    pub fn matches(&self, pattern: &str) -> Expr {
+        self.compare(Operator::Matches, regex(pattern))
+    }
+
+    /// `r.column !~ /pattern/`
+    pub fn not_matches(&self, pattern: &str) -> Expr {
+        self.compare(Operator::NotMatches, regex(pattern))
+    }
+
+    /// `exists r.column`
+    pub fn exists(&self) -> Expr {
+        Expr::Exists(self.clone())
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut chars = self.name.chars();
+        let is_identifier = chars
+            .next()
+            .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+            && chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+        if is_identifier {
+            write!(f, "r.{}", self.name)
+        } else {
+            f.write_str("r[")?;
+            write_string(f, &self.name)?;
+            f.write_str("]")
+        }
+    }
+}
+
+/// A comparison operator
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operator {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `=~`, the value being a regular expression
+    Matches,
+    /// `!~`, the value being a regular expression
+    NotMatches,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Matches => "=~",
+            Self::NotMatches => "!~",
+        })
+    }
+}
+
+/// A predicate of `filter`, combined with `&`, `|` and `!`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// A comparison of a column with a value
+    Compare {
+        /// The column
+        column: Column,
+        /// How they are compared
+        operator: Operator,
+        /// The value, escaped when written
+        value: Literal,
+    },
+    /// The column isn't null
+    Exists(Column),
+    /// Both predicates hold
+    And(Box<Self>, Box<Self>),
+    /// Either predicate holds
+    Or(Box<Self>, Box<Self>),
+    /// The predicate doesn't hold
+    Not(Box<Self>),
+}
+
+impl Expr {
+    // Higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or(..) => 0,
+            Self::And(..) => 1,
+            Self::Not(..) => 2,
+            Self::Compare { .. } | Self::Exists(_) => 3,
+        }
+    }
+
+    fn write_operand(&self, f: &mut fmt::Formatter<'_>, precedence: u8) -> fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precedence = self.precedence();
+        match self {
+            Self::Compare {
+                column,
+                operator,
+                value,
+            } => write!(f, "{} {} {}", column, operator, value),
+            Self::Exists(column) => write!(f, "exists {}", column),
+            Self::And(l, r) => {
+                l.write_operand(f, precedence)?;
+                f.write_str(" and ")?;
+                r.write_operand(f, precedence)
+            }
+            Self::Or(l, r) => {
+                l.write_operand(f, precedence)?;
+                f.write_str(" or ")?;
+                r.write_operand(f, precedence)
+            }
+            Self::Not(e) => {
+                f.write_str("not ")?;
+                e.write_operand(f, precedence)
+            }
+        }
+    }
+}
+
+impl BitAnd for Expr {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self::And(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl BitOr for Expr {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self::Or(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Not for Expr {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn literals() {
+        let cases: Vec<(Literal, &str)> = vec![
+            (r#"a "b" \c ${d} $e"#.into(), r#""a \"b\" \\c \${d} $e""#),
+            ("line\nbreak".into(), r#""line\nbreak""#),
+            (5.into(), "5"),
+            (5u64.into(), "uint(v: 5)"),
+            (1.0.into(), "1.0"),
+            (0.25.into(), "0.25"),
+            (f64::NEG_INFINITY.into(), r#"float(v: "-Inf")"#),
+            (true.into(), "true"),
+            (Duration::zero().into(), "0s"),
+            (Duration::minutes(-90).into(), "-1h30m"),
+            (
+                (Duration::days(2) + Duration::milliseconds(5)).into(),
+                "2d5ms",
+            ),
+            (
+                DateTime::parse_from_rfc3339("2021-01-01T02:00:00.5+02:00")
+                    .unwrap()
+                    .into(),
+                "2021-01-01T00:00:00.500Z",
+            ),
+            (regex("^a/b$"), r"/^a\/b$/"),
+            (Literal::Now, "now()"),
+        ];
+        for (literal, expected) in cases {
+            assert_eq!(literal.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn predicates() {
+        let cpu = measurement().eq("cpu");
+        let hosts = tag("host").eq("a") | tag("host").matches("^b");
+        assert_eq!(
+            (cpu.clone() & hosts.clone()).to_string(),
+            r#"r._measurement == "cpu" and (r.host == "a" or r.host =~ /^b/)"#
+        );
+        assert_eq!(
+            (cpu.clone() | !hosts).to_string(),
+            r#"r._measurement == "cpu" or not (r.host == "a" or r.host =~ /^b/)"#
+        );
+        assert_eq!(
+            (!cpu & col("my tag").exists() & value().ge(0.5)).to_string(),
+            r#"not r._measurement == "cpu" and exists r["my tag"] and r._value >= 0.5"#
+        );
+
+        // Values are escaped however the predicate is built
+        let injected = Expr::Compare {
+            column: tag("host"),
+            operator: Operator::Eq,
+            value: r#"a" or true or r.x == ""#.into(),
+        };
+        assert_eq!(injected, tag("host").eq(r#"a" or true or r.x == ""#));
+        assert_eq!(
+            injected.to_string(),
+            r#"r.host == "a\" or true or r.x == \"""#
+        );
+    }
+
+    #[test]
+    fn query() {
+        let start = DateTime::parse_from_rfc3339("2021-01-01T00:00:00Z").unwrap();
+        let query = from(r#"my "bucket""#)
+            .range(start, Literal::Now)
+            .filter(field().ne("usage"))
+            .pivot_fields()
+            .group(&["host"])
+            .sort(&["_time"], true)
+            .keep(&["_time", "host", "usage"])
+            .limit(10)
+            .yield_("result");
+        assert_eq!(
+            query.to_string(),
+            r#"from(bucket: "my \"bucket\"")
+  |> range(start: 2021-01-01T00:00:00Z, stop: now())
+  |> filter(fn: (r) => r._field != "usage")
+  |> pivot(rowKey: ["_time"], columnKey: ["_field"], valueColumn: "_value")
+  |> group(columns: ["host"])
+  |> sort(columns: ["_time"], desc: true)
+  |> keep(columns: ["_time", "host", "usage"])
+  |> limit(n: 10)
+  |> yield(name: "result")"#
+        );
+        assert_eq!(Query::from(query.clone()).query, query.to_string());
+    }
+}
@@ -35,19 +35,10 @@
 //! pub struct StockPrice {
 //!     ticker: String,
 //!     value: f64,
+//!     #[influxdb(rename = "_time")]
 //!     time: DateTime<FixedOffset>,
 //! }
 //!
-//! impl Default for StockPrice {
-//!     fn default() -> Self {
-//!         Self {
-//!             ticker: "".to_string(),
-//!             value: 0_f64,
-//!             time: chrono::MIN_DATETIME.with_timezone(&chrono::FixedOffset::east(7 * 3600)),
-//!         }
-//!     }
-//! }
-//!
 //! fn example() -> Result<(), Box<dyn std::error::Error>> {
 //!     let host = std::env::var("INFLUXDB_HOST").unwrap();
 //!     let org = std::env::var("INFLUXDB_ORG").unwrap();
@@ -101,26 +92,40 @@
 
 use std::io;
 use secrecy::{ExposeSecret, Secret};
-use snafu::{ResultExt, Snafu};
+use snafu::{OptionExt, ResultExt, Snafu};
 use serde::Serialize;
 use ureq::http::uri::{InvalidUriParts};
 use ureq::http::{StatusCode, Uri};
 use ureq::typestate::{WithBody, WithoutBody};
 use ureq::RequestBuilder;
 
+use crate::api::write::DefaultTags;
+
 /// Errors that occur while making requests to the Influx server.
+///
+/// More errors may be added, e.g. by features, so matches need a wildcard
+/// arm.
 #[derive(Debug, Snafu)]
+#[non_exhaustive]
 pub enum RequestError {
     /// failed to serialise the request query parameters
     UriBuilding {
+        /// The underlying error object from `serde_urlencoded`.
         source: serde_urlencoded::ser::Error,
     },
     /// failed to build the request url
     RequestBuilding {
+        /// The underlying error object from `http`.
         source: InvalidUriParts,
     },
     /// While building the request body encountered an IO error
     BodyBuilding {
+        /// The underlying IO error.
+        source: io::Error
+    },
+    /// While reading the response body encountered an IO error
+    BodyReading {
+        /// The underlying IO error.
         source: io::Error
     },
     /// While making a request to the Influx server, the underlying `reqwest`
@@ -155,6 +160,41 @@ pub enum RequestError {
         /// Error description.
         text: String,
     },
+
+    /// A query result row couldn't be converted with `FromMap`.
+    #[snafu(display("Unable to convert query result: {}", source))]
+    RecordMapping {
+        /// The missing or mistyped key.
+        source: influxdb2_structmap::FromMapError,
+    },
+
+    /// A query result row couldn't be deserialized into the requested type.
+    #[snafu(display("Unable to deserialize query result: {}", source))]
+    RecordDeserializing {
+        /// The underlying serde error.
+        source: de::Error,
+    },
+
+    /// A point to write doesn't match the `WriteSchema` it was checked
+    /// against.
+    #[snafu(display("Point doesn't match the write schema: {}", source))]
+    SchemaValidation {
+        /// The schema mismatch.
+        source: models::SchemaError,
+    },
+
+    /// The query was cancelled with its `CancellationToken`.
+    #[snafu(display("The query was cancelled"))]
+    Cancelled,
+
+    /// A Polars data frame couldn't be built from a query result, or
+    /// written as line protocol.
+    #[cfg(feature = "polars")]
+    #[snafu(display("Error while converting a data frame: {}", source))]
+    DataFrameConversion {
+        /// The underlying error object from `polars`.
+        source: polars::error::PolarsError,
+    },
 }
 
 /// Client to a server supporting the InfluxData 2.0 API.
@@ -165,6 +205,9 @@ pub struct Client {
     /// The organization tied to this client
     pub org: String,
     auth_header: Option<Secret<String>>,
+    default_tags: DefaultTags,
+    // Sends the queries that have a `CancellationToken`
+    cancellable: ureq::Agent,
 }
 
 impl Client {
@@ -212,7 +255,7 @@ impl Client {
     }
 
     /// Join base Url of the client to target API endpoint into valid Url
-    fn url(&self, mut endpoint: &str) -> Result<Uri, RequestError> {
+    fn url(&self, endpoint: &str) -> Result<Uri, RequestError> {
         let mut parts = self.base.clone().into_parts();
         let endpoint = if endpoint.starts_with('/') {
             endpoint.to_string()
@@ -225,7 +268,12 @@ impl Client {
     fn url_with_params(&self, endpoint: &str, query: impl Serialize) -> Result<Uri, RequestError> {
         let mut parts = self.base.clone().into_parts();
         let query = serde_urlencoded::to_string(query).context(UriBuilding)?;
-        parts.path_and_query = Some(format!("{endpoint}?{query}").parse().unwrap());
+        let path_and_query = if query.is_empty() {
+            endpoint.to_string()
+        } else {
+            format!("{endpoint}?{query}")
+        };
+        parts.path_and_query = Some(path_and_query.parse().unwrap());
         Uri::from_parts(parts).context(RequestBuilding)
     }
 }
@@ -239,6 +287,18 @@ pub enum BuildError {
         /// Reqwest internal error
         source: ureq::Error,
     },
+    /// A default tag was read from an environment variable that isn't set
+    #[snafu(display(
+        "Environment variable `{}` for default tag `{}` is not set",
+        var,
+        tag
+    ))]
+    DefaultTagEnvMissing {
+        /// The tag key
+        tag: String,
+        /// The environment variable name
+        var: String,
+    },
 }
 /// ClientBuilder builds the `Client`
 #[derive(Debug)]
@@ -248,6 +308,9 @@ pub struct ClientBuilder {
     /// The organization tied to this client
     pub org: String,
     auth_header: Option<Secret<String>>,
+    default_tags: DefaultTags,
+    // (tag key, environment variable), resolved on `build`
+    default_tags_from_env: Vec<(String, String)>,
 }
 
 impl ClientBuilder {
@@ -274,20 +337,85 @@ impl ClientBuilder {
             base,
             org: org.into(),
             auth_header,
+            default_tags: DefaultTags::new(),
+            default_tags_from_env: Vec::new(),
         }
     }
 
+    /// Add a tag to every point written by the client. Tags set on the point
+    /// itself take precedence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = influxdb2::ClientBuilder::new("http://localhost:8086", "org", "my-token")
+    ///     .default_tag("region", "us-west")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn default_tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_tags.insert(key, value);
+        self
+    }
+
+    /// Add several tags to every point written by the client.
+    pub fn default_tags<K, V>(mut self, tags: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (key, value) in tags {
+            self.default_tags.insert(key, value);
+        }
+        self
+    }
+
+    /// Add a tag to every point written by the client, taking its value from
+    /// the environment variable `var`. The variable is read when the client
+    /// is built.
+    pub fn default_tag_from_env(mut self, key: impl Into<String>, var: impl Into<String>) -> Self {
+        self.default_tags_from_env.push((key.into(), var.into()));
+        self
+    }
+
+    /// Add a tag to every point of `measurement` written by the client.
+    /// Measurement-level tags take precedence over the ones added with
+    /// `default_tag`.
+    pub fn measurement_default_tag(
+        mut self,
+        measurement: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.default_tags.insert_for_measurement(measurement, key, value);
+        self
+    }
+
     /// Build returns the influx client
     pub fn build(self) -> Result<Client, BuildError> {
+        let mut default_tags = self.default_tags;
+        for (tag, var) in self.default_tags_from_env {
+            let value = std::env::var(&var)
+                .ok()
+                .context(DefaultTagEnvMissing { tag: &tag, var })?;
+            default_tags.insert(tag, value);
+        }
+
         Ok(Client {
             base: self.base,
             org: self.org,
             auth_header: self.auth_header,
+            default_tags,
+            cancellable: api::cancel::agent(),
         })
     }
 }
 
 pub mod common;
+pub mod de;
+pub mod export;
+pub mod flux;
+pub mod lp;
 
 pub mod api;
 pub mod models;
@@ -0,0 +1,1170 @@
+//! Line protocol serialization with serde
+//!
+//! Writes any struct implementing `Serialize` as a line protocol point. Each
+//! named field becomes a field of the point, unless the `TagSpec` makes it a
+//! tag or the timestamp.
+//!
+//! - `None` tags and fields are left out, including nested options
+//! - unit enum variants are written as their name, newtype variants and
+//!   structs as their content
+//! - maps, e.g. from `#[serde(flatten)]`, are merged into the point
+//! - timestamps can be integers, RFC 3339 strings such as a serialized
+//!   `chrono::DateTime`, or a serialized `SystemTime`
+//!
+//! # Example
+//!
+//! ```
+//! use influxdb2::lp::{self, TagSpec};
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Cpu {
+//!     host: String,
+//!     usage: f64,
+//!     time: i64,
+//! }
+//!
+//! let cpu = Cpu { host: "server01".into(), usage: 0.5, time: 1 };
+//! let line = lp::to_string(&cpu, "cpu", &TagSpec::new().tag("host")).unwrap();
+//! assert_eq!(line, "cpu,host=server01 usage=0.5 1\n");
+//! ```
+
+use chrono::DateTime;
+use serde::ser::{self, Impossible, Serialize};
+use snafu::{ResultExt, Snafu};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::io;
+use std::sync::Arc;
+
+use crate::api::write::TimestampPrecision;
+use crate::models::data_point::DataPointError;
+use crate::models::{DataPoint, FieldValue, WriteDataPoint};
+
+/// Errors that occur while serializing a value as line protocol
+#[derive(Debug, Snafu)]
+pub enum Error {
+    /// Returned by a `Serialize` implementation
+    #[snafu(display("{}", message))]
+    Custom {
+        /// The error message
+        message: String,
+    },
+    /// The value is not a struct or a map
+    #[snafu(display("Only structs and maps can be written as points, got {}", kind))]
+    NotAStruct {
+        /// The kind of value
+        kind: &'static str,
+    },
+    /// The value of a tag or field is not a scalar
+    #[snafu(display("`{}` can't be written as a tag or field, got {}", key, kind))]
+    UnsupportedValue {
+        /// The tag or field key
+        key: String,
+        /// The kind of value
+        kind: &'static str,
+    },
+    /// A map key is not a string
+    #[snafu(display("Map keys must be strings"))]
+    KeyMustBeString,
+    /// The timestamp is not an integer, an RFC 3339 string or a `SystemTime`
+    #[snafu(display("`{}` is not a valid timestamp", key))]
+    InvalidTimestamp {
+        /// The timestamp key
+        key: String,
+    },
+    /// The point couldn't be built, e.g. because it has no fields
+    #[snafu(display("Unable to build the point: {}", source))]
+    Building {
+        /// Underlying error
+        source: DataPointError,
+    },
+    /// The line couldn't be written
+    #[snafu(display("Unable to write line protocol: {}", source))]
+    Writing {
+        /// Underlying error
+        source: io::Error,
+    },
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Which serialized fields are tags and which one is the timestamp
+///
+/// By default, every field is a field of the point except `time`, which is
+/// the timestamp in nanoseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagSpec {
+    tags: BTreeSet<String>,
+    tag_prefix: Option<String>,
+    timestamp: Option<String>,
+    precision: TimestampPrecision,
+}
+
+impl Default for TagSpec {
+    fn default() -> Self {
+        Self {
+            tags: BTreeSet::new(),
+            tag_prefix: None,
+            timestamp: Some("time".to_string()),
+            precision: TimestampPrecision::Nanoseconds,
+        }
+    }
+}
+
+impl TagSpec {
+    /// Create the default spec, without tags and with `time` as timestamp.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the field `name` as a tag.
+    pub fn tag(mut self, name: impl Into<String>) -> Self {
+        self.tags.insert(name.into());
+        self
+    }
+
+    /// Write fields whose name starts with `prefix` as tags, keyed by the rest
+    /// of their name.
+    pub fn tag_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.tag_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Use the field `name` as timestamp.
+    pub fn timestamp(mut self, name: impl Into<String>) -> Self {
+        self.timestamp = Some(name.into());
+        self
+    }
+
+    /// Don't use any field as timestamp, letting the server set it.
+    pub fn without_timestamp(mut self) -> Self {
+        self.timestamp = None;
+        self
+    }
+
+    /// Precision of timestamps converted from dates, which must match the
+    /// precision the points are written with. Integer timestamps are written
+    /// as is.
+    pub fn precision(mut self, precision: TimestampPrecision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    // The key of `name` if it is a tag
+    fn tag_key<'a>(&self, name: &'a str) -> Option<&'a str> {
+        if self.tags.contains(name) {
+            return Some(name);
+        }
+        self.tag_prefix
+            .as_ref()
+            .and_then(|prefix| name.strip_prefix(prefix.as_str()))
+    }
+}
+
+/// Serialize `value` as a `DataPoint` of `measurement`.
+pub fn to_data_point<T>(value: &T, measurement: &str, spec: &TagSpec) -> Result<DataPoint, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let point = value.serialize(PointSerializer::new(spec))?;
+
+    let mut builder = DataPoint::builder(measurement);
+    for (key, value) in point.tags {
+        builder = builder.tag(key, value);
+    }
+    for (key, value) in point.fields {
+        builder = builder.field(key, value);
+    }
+    if let Some(timestamp) = point.timestamp {
+        builder = builder.timestamp(timestamp);
+    }
+    builder.build().context(Building)
+}
+
+/// Serialize `value` as a line of `measurement` into `writer`.
+pub fn to_writer<W, T>(writer: W, value: &T, measurement: &str, spec: &TagSpec) -> Result<(), Error>
+where
+    W: io::Write,
+    T: Serialize + ?Sized,
+{
+    to_data_point(value, measurement, spec)?
+        .write_data_point_to(writer)
+        .context(Writing)
+}
+
+/// Serialize `value` as a line of `measurement`.
+pub fn to_string<T>(value: &T, measurement: &str, spec: &TagSpec) -> Result<String, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let mut buf = Vec::new();
+    to_writer(&mut buf, value, measurement, spec)?;
+    Ok(String::from_utf8(buf).expect("line protocol is valid UTF-8"))
+}
+
+/// A `Serialize` value written as a point by `Client::write`
+///
+/// Values without any field are skipped, like with the `WriteDataPoint`
+/// derive.
+#[derive(Clone, Debug)]
+pub struct Serialized<T> {
+    value: T,
+    measurement: Arc<str>,
+    spec: Arc<TagSpec>,
+}
+
+impl<T> Serialized<T> {
+    /// Write `value` as a point of `measurement`.
+    pub fn new(value: T, measurement: impl Into<String>, spec: TagSpec) -> Self {
+        Self {
+            value,
+            measurement: measurement.into().into(),
+            spec: Arc::new(spec),
+        }
+    }
+}
+
+impl<T: Serialize> WriteDataPoint for Serialized<T> {
+    fn write_data_point_to<W>(&self, w: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        match to_data_point(&self.value, &self.measurement, &self.spec) {
+            Ok(point) => point.write_data_point_to(w),
+            Err(Error::Building {
+                source: DataPointError::AtLeastOneFieldRequired { .. },
+            }) => Ok(()),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+}
+
+/// Write each of `values` as a point of `measurement`.
+///
+/// # Example
+///
+/// ```no_run
+/// use influxdb2::lp::{self, TagSpec};
+/// use influxdb2::Client;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Cpu {
+///     host: String,
+///     usage: f64,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new("http://localhost:8086", "org", "token");
+/// let cpus = vec![Cpu { host: "server01".into(), usage: 0.5 }];
+/// client.write("bucket", lp::points(cpus, "cpu", TagSpec::new().tag("host")))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn points<I>(
+    values: I,
+    measurement: impl Into<String>,
+    spec: TagSpec,
+) -> impl Iterator<Item = Serialized<I::Item>>
+where
+    I: IntoIterator,
+{
+    let measurement: Arc<str> = measurement.into().into();
+    let spec = Arc::new(spec);
+    values.into_iter().map(move |value| Serialized {
+        value,
+        measurement: Arc::clone(&measurement),
+        spec: Arc::clone(&spec),
+    })
+}
+
+#[derive(Default)]
+struct Point {
+    tags: BTreeMap<String, String>,
+    fields: BTreeMap<String, FieldValue>,
+    timestamp: Option<i64>,
+}
+
+impl Point {
+    fn insert<T>(&mut self, spec: &TagSpec, key: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        if spec.timestamp.as_deref() == Some(key) {
+            self.timestamp = value.serialize(TimestampSerializer { key, spec })?;
+        } else if let Some(tag_key) = spec.tag_key(key) {
+            if let Some(value) = value.serialize(ValueSerializer { key })? {
+                self.tags.insert(tag_key.to_string(), tag_value(value));
+            }
+        } else if let Some(value) = value.serialize(ValueSerializer { key })? {
+            self.fields.insert(key.to_string(), value);
+        }
+        Ok(())
+    }
+}
+
+fn tag_value(value: FieldValue) -> String {
+    match value {
+        FieldValue::Bool(v) => v.to_string(),
+        FieldValue::F64(v) => v.to_string(),
+        FieldValue::I64(v) => v.to_string(),
+        FieldValue::U64(v) => v.to_string(),
+        FieldValue::String(v) => v,
+    }
+}
+
+// Serializes a struct or map into a `Point`
+struct PointSerializer<'a> {
+    spec: &'a TagSpec,
+    point: Point,
+    // Key of the map entry whose value comes next
+    pending_key: Option<String>,
+}
+
+impl<'a> PointSerializer<'a> {
+    fn new(spec: &'a TagSpec) -> Self {
+        Self {
+            spec,
+            point: Point::default(),
+            pending_key: None,
+        }
+    }
+}
+
+impl<'a> PointSerializer<'a> {
+    fn not_a_struct<T>(kind: &'static str) -> Result<T, Error> {
+        NotAStruct { kind }.fail()
+    }
+}
+
+impl<'a> ser::Serializer for PointSerializer<'a> {
+    type Ok = Point;
+    type Error = Error;
+    type SerializeSeq = Impossible<Point, Error>;
+    type SerializeTuple = Impossible<Point, Error>;
+    type SerializeTupleStruct = Impossible<Point, Error>;
+    type SerializeTupleVariant = Impossible<Point, Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, _: bool) -> Result<Point, Error> {
+        Self::not_a_struct("a boolean")
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<Point, Error> {
+        Self::not_a_struct("an integer")
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Point, Error> {
+        Self::not_a_struct("a float")
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Point, Error> {
+        Self::not_a_struct("a float")
+    }
+
+    fn serialize_char(self, _: char) -> Result<Point, Error> {
+        Self::not_a_struct("a string")
+    }
+
+    fn serialize_str(self, _: &str) -> Result<Point, Error> {
+        Self::not_a_struct("a string")
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Point, Error> {
+        Self::not_a_struct("bytes")
+    }
+
+    fn serialize_none(self) -> Result<Point, Error> {
+        Self::not_a_struct("none")
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Point, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Point, Error> {
+        Self::not_a_struct("a unit")
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Point, Error> {
+        Self::not_a_struct("a unit struct")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Point, Error> {
+        Self::not_a_struct("a unit variant")
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<Point, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Point, Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Self::not_a_struct("a sequence")
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Self::not_a_struct("a tuple")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Self::not_a_struct("a tuple struct")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Self::not_a_struct("a tuple variant")
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self, Error> {
+        Ok(self)
+    }
+}
+
+impl<'a> ser::SerializeStruct for PointSerializer<'a> {
+    type Ok = Point;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.point.insert(self.spec, key, value)
+    }
+
+    fn end(self) -> Result<Point, Error> {
+        Ok(self.point)
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for PointSerializer<'a> {
+    type Ok = Point;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.point.insert(self.spec, key, value)
+    }
+
+    fn end(self) -> Result<Point, Error> {
+        Ok(self.point)
+    }
+}
+
+// Map entries are serialized as fields keyed by the serialized key
+impl<'a> ser::SerializeMap for PointSerializer<'a> {
+    type Ok = Point;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.pending_key = Some(map_key(key)?);
+        Ok(())
+    }
+
+    // A flattened struct variant is written as its name, then its fields as
+    // a map, which are merged into the point.
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = match self.pending_key.take() {
+            Some(key) => key,
+            None => return KeyMustBeString.fail(),
+        };
+        match value.serialize(PointSerializer::new(self.spec)) {
+            Ok(point) => {
+                self.point.tags.extend(point.tags);
+                self.point.fields.extend(point.fields);
+                if point.timestamp.is_some() {
+                    self.point.timestamp = point.timestamp;
+                }
+                Ok(())
+            }
+            Err(Error::NotAStruct { .. }) => self.point.insert(self.spec, &key, value),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Error>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        let key = map_key(key)?;
+        self.point.insert(self.spec, &key, value)
+    }
+
+    fn end(self) -> Result<Point, Error> {
+        Ok(self.point)
+    }
+}
+
+fn map_key<T>(key: &T) -> Result<String, Error>
+where
+    T: Serialize + ?Sized,
+{
+    match key.serialize(ValueSerializer { key: "" }) {
+        Ok(Some(FieldValue::String(key))) => Ok(key),
+        _ => KeyMustBeString.fail(),
+    }
+}
+
+// Serializes a scalar into a tag or field value, `None` if it is left out
+struct ValueSerializer<'a> {
+    key: &'a str,
+}
+
+impl<'a> ValueSerializer<'a> {
+    fn unsupported<T>(&self, kind: &'static str) -> Result<T, Error> {
+        UnsupportedValue {
+            key: self.key,
+            kind,
+        }
+        .fail()
+    }
+}
+
+type ValueResult = Result<Option<FieldValue>, Error>;
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = Option<FieldValue>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<FieldValue>, Error>;
+    type SerializeTuple = Impossible<Option<FieldValue>, Error>;
+    type SerializeTupleStruct = Impossible<Option<FieldValue>, Error>;
+    type SerializeTupleVariant = Impossible<Option<FieldValue>, Error>;
+    type SerializeMap = Impossible<Option<FieldValue>, Error>;
+    type SerializeStruct = Impossible<Option<FieldValue>, Error>;
+    type SerializeStructVariant = Impossible<Option<FieldValue>, Error>;
+
+    fn serialize_bool(self, v: bool) -> ValueResult {
+        Ok(Some(v.into()))
+    }
+
+    fn serialize_i8(self, v: i8) -> ValueResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> ValueResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> ValueResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> ValueResult {
+        Ok(Some(v.into()))
+    }
+
+    fn serialize_u8(self, v: u8) -> ValueResult {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> ValueResult {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> ValueResult {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> ValueResult {
+        Ok(Some(v.into()))
+    }
+
+    fn serialize_f32(self, v: f32) -> ValueResult {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> ValueResult {
+        Ok(Some(v.into()))
+    }
+
+    fn serialize_char(self, v: char) -> ValueResult {
+        Ok(Some(v.to_string().into()))
+    }
+
+    fn serialize_str(self, v: &str) -> ValueResult {
+        Ok(Some(v.into()))
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> ValueResult {
+        self.unsupported("bytes")
+    }
+
+    fn serialize_none(self) -> ValueResult {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> ValueResult
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> ValueResult {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> ValueResult {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> ValueResult {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> ValueResult
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        value: &T,
+    ) -> ValueResult
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.unsupported("a sequence")
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        self.unsupported("a tuple")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.unsupported("a tuple struct")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.unsupported("a tuple variant")
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.unsupported("a map, use #[serde(flatten)] to embed it")
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        self.unsupported("a struct, use #[serde(flatten)] to embed it")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.unsupported("a struct variant")
+    }
+}
+
+// Serializes a timestamp, `None` if it is left out
+struct TimestampSerializer<'a> {
+    key: &'a str,
+    spec: &'a TagSpec,
+}
+
+impl<'a> TimestampSerializer<'a> {
+    fn invalid<T>(&self) -> Result<T, Error> {
+        InvalidTimestamp { key: self.key }.fail()
+    }
+
+    fn nanos_timestamp(&self, nanos: i128) -> Result<Option<i64>, Error> {
+        let timestamp = self.spec.precision.convert_nanos(nanos);
+        match i64::try_from(timestamp) {
+            Ok(timestamp) => Ok(Some(timestamp)),
+            Err(_) => self.invalid(),
+        }
+    }
+}
+
+type TimestampResult = Result<Option<i64>, Error>;
+
+impl<'a> ser::Serializer for TimestampSerializer<'a> {
+    type Ok = Option<i64>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<i64>, Error>;
+    type SerializeTuple = Impossible<Option<i64>, Error>;
+    type SerializeTupleStruct = Impossible<Option<i64>, Error>;
+    type SerializeTupleVariant = Impossible<Option<i64>, Error>;
+    type SerializeMap = Impossible<Option<i64>, Error>;
+    type SerializeStruct = SystemTimeSerializer<'a>;
+    type SerializeStructVariant = Impossible<Option<i64>, Error>;
+
+    fn serialize_bool(self, _: bool) -> TimestampResult {
+        self.invalid()
+    }
+
+    fn serialize_i8(self, v: i8) -> TimestampResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> TimestampResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> TimestampResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> TimestampResult {
+        Ok(Some(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> TimestampResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> TimestampResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> TimestampResult {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> TimestampResult {
+        match i64::try_from(v) {
+            Ok(v) => Ok(Some(v)),
+            Err(_) => self.invalid(),
+        }
+    }
+
+    fn serialize_f32(self, _: f32) -> TimestampResult {
+        self.invalid()
+    }
+
+    fn serialize_f64(self, _: f64) -> TimestampResult {
+        self.invalid()
+    }
+
+    fn serialize_char(self, _: char) -> TimestampResult {
+        self.invalid()
+    }
+
+    fn serialize_str(self, v: &str) -> TimestampResult {
+        match DateTime::parse_from_rfc3339(v) {
+            Ok(time) => self.nanos_timestamp(
+                i128::from(time.timestamp()) * 1_000_000_000
+                    + i128::from(time.timestamp_subsec_nanos()),
+            ),
+            Err(_) => self.invalid(),
+        }
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> TimestampResult {
+        self.invalid()
+    }
+
+    fn serialize_none(self) -> TimestampResult {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> TimestampResult
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> TimestampResult {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> TimestampResult {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str) -> TimestampResult {
+        self.invalid()
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> TimestampResult
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> TimestampResult
+    where
+        T: Serialize + ?Sized,
+    {
+        self.invalid()
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.invalid()
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        self.invalid()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.invalid()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.invalid()
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.invalid()
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        if name != "SystemTime" {
+            return self.invalid();
+        }
+        Ok(SystemTimeSerializer {
+            timestamp: self,
+            secs: None,
+            nanos: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.invalid()
+    }
+}
+
+// `SystemTime` is serialized as the seconds and nanoseconds since the epoch
+struct SystemTimeSerializer<'a> {
+    timestamp: TimestampSerializer<'a>,
+    secs: Option<i64>,
+    nanos: Option<i64>,
+}
+
+impl<'a> ser::SerializeStruct for SystemTimeSerializer<'a> {
+    type Ok = Option<i64>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let part = value.serialize(TimestampSerializer {
+            key: self.timestamp.key,
+            spec: self.timestamp.spec,
+        })?;
+        match key {
+            "secs_since_epoch" => self.secs = part,
+            "nanos_since_epoch" => self.nanos = part,
+            _ => return self.timestamp.invalid(),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> TimestampResult {
+        match (self.secs, self.nanos) {
+            (Some(secs), Some(nanos)) => self
+                .timestamp
+                .nanos_timestamp(i128::from(secs) * 1_000_000_000 + i128::from(nanos)),
+            _ => self.timestamp.invalid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use serde::Serialize;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Region {
+        UsWest,
+    }
+
+    #[derive(Serialize)]
+    struct Extra {
+        tag_rack: String,
+        temperature: Option<f32>,
+    }
+
+    #[derive(Serialize)]
+    struct Cpu {
+        host: String,
+        region: Option<Region>,
+        usage: f64,
+        cores: u8,
+        note: Option<Option<String>>,
+        #[serde(flatten)]
+        extra: Extra,
+        time: i64,
+    }
+
+    fn cpu() -> Cpu {
+        Cpu {
+            host: "server 01".to_string(),
+            region: Some(Region::UsWest),
+            usage: 0.5,
+            cores: 4,
+            note: Some(None),
+            extra: Extra {
+                tag_rack: "r1".to_string(),
+                temperature: None,
+            },
+            time: 42,
+        }
+    }
+
+    #[test]
+    fn struct_to_line() {
+        let spec = TagSpec::new().tag("host").tag("region").tag_prefix("tag_");
+        assert_eq!(
+            to_string(&cpu(), "cpu", &spec).unwrap(),
+            "cpu,host=server\\ 01,rack=r1,region=us_west cores=4u,usage=0.5 42\n"
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        #[derive(Serialize)]
+        struct Point<T> {
+            value: bool,
+            at: T,
+        }
+
+        let spec = TagSpec::new()
+            .timestamp("at")
+            .precision(TimestampPrecision::Milliseconds);
+        let at = Utc.timestamp_opt(1_671_095_854, 123_456_789).unwrap();
+        assert_eq!(
+            to_string(&Point { value: true, at }, "m", &spec).unwrap(),
+            "m value=t 1671095854123\n"
+        );
+
+        let at = UNIX_EPOCH + Duration::from_millis(1_671_095_854_123);
+        assert_eq!(
+            to_string(&Point { value: true, at }, "m", &spec).unwrap(),
+            "m value=t 1671095854123\n"
+        );
+
+        let at: Option<SystemTime> = None;
+        assert_eq!(
+            to_string(&Point { value: true, at }, "m", &spec).unwrap(),
+            "m value=t\n"
+        );
+
+        let err = to_string(
+            &Point {
+                value: true,
+                at: "yesterday",
+            },
+            "m",
+            &spec,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidTimestamp { .. }), "{}", err);
+    }
+
+    #[test]
+    fn flattened_struct_variant() {
+        #[derive(Serialize)]
+        enum Kind {
+            Cpu { usage: f64 },
+        }
+
+        #[derive(Serialize)]
+        struct Sample {
+            host: String,
+            #[serde(flatten)]
+            kind: Kind,
+        }
+
+        let sample = Sample {
+            host: "a".to_string(),
+            kind: Kind::Cpu { usage: 0.5 },
+        };
+        assert_eq!(
+            to_string(&sample, "m", &TagSpec::new().tag("host")).unwrap(),
+            "m,host=a usage=0.5\n"
+        );
+    }
+
+    #[test]
+    fn unsupported_values() {
+        #[derive(Serialize)]
+        struct Nested {
+            values: Vec<i64>,
+        }
+
+        let err = to_string(&Nested { values: vec![1] }, "m", &TagSpec::new()).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedValue { .. }), "{}", err);
+
+        let err = to_string(&1, "m", &TagSpec::new()).unwrap_err();
+        assert!(matches!(err, Error::NotAStruct { .. }), "{}", err);
+    }
+
+    #[test]
+    fn serialized_points_without_fields_are_skipped() {
+        #[derive(Serialize)]
+        struct Empty {
+            value: Option<i64>,
+        }
+
+        let mut buf = Vec::new();
+        for point in points(
+            vec![Empty { value: None }, Empty { value: Some(1) }],
+            "m",
+            TagSpec::new(),
+        ) {
+            point.write_data_point_to(&mut buf).unwrap();
+        }
+        assert_eq!(String::from_utf8(buf).unwrap(), "m value=1i\n");
+    }
+
+    #[test]
+    fn serialized_points_report_errors() {
+        #[derive(Serialize)]
+        struct Listed {
+            values: Vec<i64>,
+        }
+
+        let point = Serialized::new(Listed { values: vec![1] }, "m", TagSpec::new());
+        let error = point.write_data_point_to(Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
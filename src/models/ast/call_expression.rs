@@ -2,23 +2,30 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::ast::{Expression, SourceLocation};
+
 /// Represents a function call
-#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct CallExpression {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// Callee
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub callee: Option<Box<crate::models::ast::Expression>>,
-    /// Function arguments
+    pub callee: Box<Expression>,
+    /// Function arguments, an `ObjectExpression` of the named arguments
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub arguments: Vec<crate::models::ast::Expression>,
+    pub arguments: Vec<Expression>,
+    /// Where the call is in the script, kept so that diagnostics can point at
+    /// it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
 }
 
 impl CallExpression {
     /// Represents a function call
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(callee: Expression, arguments: Vec<Expression>) -> Self {
+        Self {
+            callee: Box::new(callee),
+            arguments,
+            location: None,
+        }
     }
 }
@@ -1,84 +1,544 @@
 //! Expression
 
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
-/// Expression AST
+use crate::models::ast::{
+    Block, CallExpression, DictItem, Duration, Identifier, MemberExpression, Property,
+    StringLiteral,
+};
+
+/// A Flux expression
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum Expression {
+    /// `a`
+    Identifier(Identifier),
+    /// `[1, 2]`
+    #[serde(rename = "ArrayExpression")]
+    Array(ArrayExpression),
+    /// `["a": 1]`
+    #[serde(rename = "DictExpression")]
+    Dict(DictExpression),
+    /// `(r) => r._value`
+    #[serde(rename = "FunctionExpression")]
+    Function(FunctionExpression),
+    /// `a and b`
+    #[serde(rename = "LogicalExpression")]
+    Logical(LogicalExpression),
+    /// `{a: 1}`
+    #[serde(rename = "ObjectExpression")]
+    Object(ObjectExpression),
+    /// `r.a` or `r["a"]`
+    #[serde(rename = "MemberExpression")]
+    Member(MemberExpression),
+    /// `a[0]`
+    #[serde(rename = "IndexExpression")]
+    Index(IndexExpression),
+    /// `a + b`
+    #[serde(rename = "BinaryExpression")]
+    Binary(BinaryExpression),
+    /// `-a`, `not a` or `exists a`
+    #[serde(rename = "UnaryExpression")]
+    Unary(UnaryExpression),
+    /// `a |> f()`
+    #[serde(rename = "PipeExpression")]
+    Pipe(PipeExpression),
+    /// `f(a: 1)`
+    #[serde(rename = "CallExpression")]
+    Call(CallExpression),
+    /// `if a then b else c`
+    #[serde(rename = "ConditionalExpression")]
+    Conditional(ConditionalExpression),
+    /// `"a ${b}"`
+    #[serde(rename = "StringExpression")]
+    Interpolated(StringExpression),
+    /// `(a)`
+    #[serde(rename = "ParenExpression")]
+    Paren(ParenExpression),
+    /// `1`
+    #[serde(rename = "IntegerLiteral")]
+    Integer(IntegerLiteral),
+    /// An unsigned integer
+    #[serde(rename = "UnsignedIntegerLiteral")]
+    UnsignedInteger(UnsignedIntegerLiteral),
+    /// `1.5`
+    #[serde(rename = "FloatLiteral")]
+    Float(FloatLiteral),
+    /// `"a"`
+    #[serde(rename = "StringLiteral")]
+    String(StringLiteral),
+    /// `1h30m`
+    #[serde(rename = "DurationLiteral")]
+    Duration(DurationLiteral),
+    /// `2021-01-01T00:00:00Z`
+    #[serde(rename = "DateTimeLiteral")]
+    DateTime(DateTimeLiteral),
+    /// `true`
+    #[serde(rename = "BooleanLiteral")]
+    Boolean(BooleanLiteral),
+    /// `/a/`
+    #[serde(rename = "RegexpLiteral")]
+    Regexp(RegexpLiteral),
+    /// `<-`, the piped argument of a function
+    PipeLiteral(PipeLiteral),
+    /// `.a`
+    #[serde(rename = "LabelLiteral")]
+    Label(LabelLiteral),
+    /// An expression that couldn't be parsed
+    #[serde(rename = "BadExpression")]
+    Bad(BadExpression),
+}
+
+serialize_variants!(Expression {
+    Identifier,
+    Array,
+    Dict,
+    Function,
+    Logical,
+    Object,
+    Member,
+    Index,
+    Binary,
+    Unary,
+    Pipe,
+    Call,
+    Conditional,
+    Interpolated,
+    Paren,
+    Integer,
+    UnsignedInteger,
+    Float,
+    String,
+    Duration,
+    DateTime,
+    Boolean,
+    Regexp,
+    PipeLiteral,
+    Label,
+    Bad,
+});
+
+/// Used to create and directly specify the elements of an array object
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ArrayExpression {
+    /// Elements of the array
+    #[serde(default)]
+    pub elements: Vec<Expression>,
+}
+
+/// Used to create and directly specify the elements of a dictionary
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
-pub struct Expression {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
+#[serde(tag = "type")]
+pub struct DictExpression {
     /// Elements of the dictionary
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub elements: Vec<crate::models::ast::DictItem>,
-    /// Function parameters
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub params: Vec<crate::models::ast::Property>,
-    /// Node
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub body: Option<crate::models::ast::Node>,
-    /// Operator
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub operator: Option<String>,
-    /// Left leaf
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub left: Option<Box<crate::models::ast::Expression>>,
-    /// Right leaf
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub right: Option<Box<crate::models::ast::Expression>>,
-    /// Parent Expression
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub callee: Option<Box<crate::models::ast::Expression>>,
-    /// Function arguments
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub arguments: Vec<crate::models::ast::Expression>,
-    /// Test Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub test: Option<Box<crate::models::ast::Expression>>,
-    /// Alternate Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub alternate: Option<Box<crate::models::ast::Expression>>,
-    /// Consequent Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub consequent: Option<Box<crate::models::ast::Expression>>,
-    /// Object Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub object: Option<Box<crate::models::ast::Expression>>,
-    /// PropertyKey Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub property: Option<Box<crate::models::ast::PropertyKey>>,
-    /// Array Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub array: Option<Box<crate::models::ast::Expression>>,
-    /// Index Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub index: Option<Box<crate::models::ast::Expression>>,
-    /// Properties
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub properties: Vec<crate::models::ast::Property>,
-    /// Expression
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub expression: Option<Box<crate::models::ast::Expression>>,
-    /// Argument
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub argument: Option<Box<crate::models::ast::Expression>>,
-    /// Call Expr
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub call: Option<crate::models::ast::CallExpression>,
-    /// Expression Value
+    #[serde(default)]
+    pub elements: Vec<DictItem>,
+}
+
+/// Function literal
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct FunctionExpression {
+    /// Function parameters, with their default value if any
+    #[serde(default)]
+    pub params: Vec<Property>,
+    /// Function body
+    pub body: FunctionBody,
+}
+
+/// Body of a function, a block or a single expression
+#[derive(Clone, Debug, PartialEq)]
+pub enum FunctionBody {
+    /// `{ ... return a }`
+    Block(Block),
+    /// The returned expression
+    Expression(Box<Expression>),
+}
+
+impl Serialize for FunctionBody {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Block(block) => block.serialize(serializer),
+            Self::Expression(expression) => expression.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FunctionBody {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let body = if value.get("type").and_then(|t| t.as_str()) == Some("Block") {
+            serde_json::from_value(value).map(Self::Block)
+        } else {
+            serde_json::from_value(value).map(Self::Expression)
+        };
+        body.map_err(de::Error::custom)
+    }
+}
+
+/// Represents the rule conditions that collectively evaluate to a boolean
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct LogicalExpression {
+    /// `and` or `or`
+    pub operator: LogicalOperator,
+    /// Left operand
+    pub left: Box<Expression>,
+    /// Right operand
+    pub right: Box<Expression>,
+}
+
+/// Allows the declaration of an anonymous object within a declaration
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ObjectExpression {
+    /// The object extended with `{with ...}`
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
-    /// Duration values
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub values: Vec<crate::models::ast::Duration>,
-    /// Expression Name
+    pub with: Option<Identifier>,
+    /// Properties of the object
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
+/// Represents indexing into an array
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct IndexExpression {
+    /// The array
+    pub array: Box<Expression>,
+    /// The index
+    pub index: Box<Expression>,
+}
+
+/// Uses binary operators to act on two operands in an expression
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct BinaryExpression {
+    /// The operator
+    pub operator: Operator,
+    /// Left operand
+    pub left: Box<Expression>,
+    /// Right operand
+    pub right: Box<Expression>,
+}
+
+/// Expression with one operator and one operand
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UnaryExpression {
+    /// The operator
+    pub operator: Operator,
+    /// The operand
+    pub argument: Box<Expression>,
+}
+
+/// Call expression with pipe argument
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct PipeExpression {
+    /// The piped value
+    pub argument: Box<Expression>,
+    /// The function it is piped into
+    pub call: CallExpression,
+}
+
+/// Selects one of two expressions, `consequent` or `alternate`, depending
+/// on a third, boolean, expression, `test`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ConditionalExpression {
+    /// The condition
+    pub test: Box<Expression>,
+    /// Value when the condition holds
+    pub consequent: Box<Expression>,
+    /// Value otherwise
+    pub alternate: Box<Expression>,
+}
+
+/// A string with interpolated expressions
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct StringExpression {
+    /// Text and interpolated parts, in order
+    #[serde(default)]
+    pub parts: Vec<StringExpressionPart>,
+}
+
+/// Part of a `StringExpression`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum StringExpressionPart {
+    /// Literal text
+    #[serde(rename = "TextPart")]
+    Text(TextPart),
+    /// `${expression}`
+    #[serde(rename = "InterpolatedPart")]
+    Interpolated(InterpolatedPart),
+}
+
+serialize_variants!(StringExpressionPart { Text, Interpolated });
+
+/// Literal text of a `StringExpression`
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct TextPart {
+    /// The text, unescaped
+    pub value: String,
+}
+
+/// Interpolated expression of a `StringExpression`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct InterpolatedPart {
+    /// The expression
+    pub expression: Box<Expression>,
+}
+
+/// An expression in parentheses
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ParenExpression {
+    /// The expression
+    pub expression: Box<Expression>,
+}
+
+/// Represents integer numbers
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct IntegerLiteral {
+    /// The value, a string in JSON
+    #[serde(with = "number_string")]
+    pub value: i64,
+}
+
+/// Represents unsigned integer numbers
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UnsignedIntegerLiteral {
+    /// The value, a string in JSON
+    #[serde(with = "number_string")]
+    pub value: u64,
+}
+
+/// Represents floating point numbers according to the double
+/// representations defined by the IEEE-754-1985
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct FloatLiteral {
+    /// The value
+    pub value: f64,
+}
+
+/// Represents the elapsed time between two instants as an int64
+/// nanosecond count with syntax of golang's time.Duration
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct DurationLiteral {
+    /// Magnitudes and units, e.g. `1h` and `30m` in `1h30m`
+    #[serde(default)]
+    pub values: Vec<Duration>,
+}
+
+/// Represents an instant in time with nanosecond precision using the syntax
+/// of golang's RFC3339 Nanosecond variant
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct DateTimeLiteral {
+    /// The value, an RFC 3339 string in JSON
+    #[serde(serialize_with = "rfc3339")]
+    pub value: DateTime<FixedOffset>,
+}
+
+/// Represents boolean values
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct BooleanLiteral {
+    /// The value
+    pub value: bool,
+}
+
+/// Expressions begin and end with `/` and are regular expressions with
+/// syntax accepted by RE2
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RegexpLiteral {
+    /// The pattern, without the enclosing `/`
+    pub value: String,
+}
+
+/// Represents a specialized literal value, indicating the left hand value
+/// of a pipe expression
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct PipeLiteral {}
+
+/// Represents the name of a record label, e.g. `.a`
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct LabelLiteral {
+    /// The label
+    pub value: String,
+}
+
+/// An expression that couldn't be parsed
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct BadExpression {
+    /// Raw source text
+    pub text: String,
+    /// What could be parsed of it
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub name: Option<String>,
+    pub expression: Option<Box<Expression>>,
+}
+
+/// Operator of a `BinaryExpression` or `UnaryExpression`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Operator {
+    /// `*`
+    #[serde(rename = "*")]
+    Multiplication,
+    /// `/`
+    #[serde(rename = "/")]
+    Division,
+    /// `%`
+    #[serde(rename = "%")]
+    Modulo,
+    /// `^`
+    #[serde(rename = "^")]
+    Power,
+    /// `+`
+    #[serde(rename = "+")]
+    Addition,
+    /// `-`
+    #[serde(rename = "-")]
+    Subtraction,
+    /// `<=`
+    #[serde(rename = "<=")]
+    LessThanEqual,
+    /// `<`
+    #[serde(rename = "<")]
+    LessThan,
+    /// `>=`
+    #[serde(rename = ">=")]
+    GreaterThanEqual,
+    /// `>`
+    #[serde(rename = ">")]
+    GreaterThan,
+    /// `startswith`
+    #[serde(rename = "startswith")]
+    StartsWith,
+    /// `in`
+    #[serde(rename = "in")]
+    In,
+    /// `not`
+    #[serde(rename = "not")]
+    Not,
+    /// `exists`
+    #[serde(rename = "exists")]
+    Exists,
+    /// `not empty`
+    #[serde(rename = "not empty")]
+    NotEmpty,
+    /// `empty`
+    #[serde(rename = "empty")]
+    Empty,
+    /// `==`
+    #[serde(rename = "==")]
+    Equal,
+    /// `!=`
+    #[serde(rename = "!=")]
+    NotEqual,
+    /// `=~`
+    #[serde(rename = "=~")]
+    RegexpMatch,
+    /// `!~`
+    #[serde(rename = "!~")]
+    NotRegexpMatch,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Multiplication => "*",
+            Self::Division => "/",
+            Self::Modulo => "%",
+            Self::Power => "^",
+            Self::Addition => "+",
+            Self::Subtraction => "-",
+            Self::LessThanEqual => "<=",
+            Self::LessThan => "<",
+            Self::GreaterThanEqual => ">=",
+            Self::GreaterThan => ">",
+            Self::StartsWith => "startswith",
+            Self::In => "in",
+            Self::Not => "not",
+            Self::Exists => "exists",
+            Self::NotEmpty => "not empty",
+            Self::Empty => "empty",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::RegexpMatch => "=~",
+            Self::NotRegexpMatch => "!~",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Operator of a `LogicalExpression`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogicalOperator {
+    /// `and`
+    And,
+    /// `or`
+    Or,
+}
+
+impl fmt::Display for LogicalOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::And => f.write_str("and"),
+            Self::Or => f.write_str("or"),
+        }
+    }
 }
 
-impl Expression {
-    /// Return instance of expression
-    pub fn new() -> Self {
-        Self::default()
+// Integers are strings in the JSON of the AST, to not lose precision.
+mod number_string {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub(super) fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub(super) fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr + Deserialize<'de>,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr<T> {
+            Number(T),
+            String(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(v) => Ok(v),
+            Repr::String(s) => s.parse().map_err(de::Error::custom),
+        }
     }
 }
+
+fn rfc3339<S: Serializer>(value: &DateTime<FixedOffset>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
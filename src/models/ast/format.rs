@@ -0,0 +1,617 @@
+//! Formatting of the AST back into Flux source
+//!
+//! `Package`, `File`, `Statement` and `Expression` implement `Display`, so a
+//! query can be parsed with `Client::query_ast`, modified and turned back into
+//! a script with `to_string()`. The output is canonical: pipe calls go on
+//! their own line, blocks are indented by four spaces, and statements of a
+//! different kind, or spanning several lines, are set apart by an empty line.
+//! Parentheses are added where operator precedence requires them.
+
+use chrono::SecondsFormat;
+use serde_json::Value;
+use std::fmt;
+
+use crate::flux::write_escaped;
+use crate::models::ast::{
+    Assignment, Block, CallExpression, Expression, FunctionBody, FunctionExpression,
+    LogicalOperator, MemberAssignment, MemberExpression, ObjectExpression, Operator, Package,
+    Property, PropertyKey, Statement, StringExpressionPart, VariableAssignment,
+};
+use crate::models::File;
+
+const INDENT: &str = "    ";
+
+impl fmt::Display for Package {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, file) in self.files.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", file)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::default();
+        printer.file(self);
+        f.write_str(&printer.out)
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::default();
+        printer.statement(self);
+        f.write_str(&printer.out)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::default();
+        printer.expression(self);
+        f.write_str(&printer.out)
+    }
+}
+
+// How loosely an expression binds, operands binding more loosely than their
+// operator need parentheses.
+fn precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::Pipe(_) => 2,
+        Expression::Unary(unary) => match unary.operator {
+            Operator::Not | Operator::Exists => 8,
+            _ => 3,
+        },
+        Expression::Binary(binary) => match binary.operator {
+            Operator::Power => 4,
+            Operator::Multiplication | Operator::Division | Operator::Modulo => 5,
+            Operator::Addition | Operator::Subtraction => 6,
+            _ => 7,
+        },
+        Expression::Logical(logical) => match logical.operator {
+            LogicalOperator::And => 9,
+            LogicalOperator::Or => 10,
+        },
+        Expression::Conditional(_) => 11,
+        Expression::Function(_) => 12,
+        _ => 1,
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn push(&mut self, s: &str) {
+        self.out.push_str(s);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn list<T>(&mut self, items: &[T], mut item: impl FnMut(&mut Self, &T)) {
+        for (i, x) in items.iter().enumerate() {
+            if i > 0 {
+                self.push(", ");
+            }
+            item(self, x);
+        }
+    }
+
+    fn file(&mut self, file: &File) {
+        let mut sections = Vec::new();
+        if let Some(package) = &file.package {
+            sections.push(format!("package {}", package.name.name));
+        }
+        if !file.imports.is_empty() {
+            let mut imports = Self::default();
+            for (i, import) in file.imports.iter().enumerate() {
+                if i > 0 {
+                    imports.newline();
+                }
+                imports.push("import ");
+                if let Some(alias) = &import.r#as {
+                    imports.push(&alias.name);
+                    imports.push(" ");
+                }
+                imports.string(&import.path.value);
+            }
+            sections.push(imports.out);
+        }
+        if !file.body.is_empty() {
+            let mut body = Self::default();
+            body.statements(&file.body);
+            sections.push(body.out);
+        }
+
+        if !sections.is_empty() {
+            self.push(&sections.join("\n\n"));
+            self.push("\n");
+        }
+    }
+
+    // Writes the statements one per line, starting on the current line.
+    fn statements(&mut self, body: &[Statement]) {
+        let mut previous: Option<(&Statement, bool)> = None;
+        for statement in body {
+            let mut printer = Self {
+                out: String::new(),
+                indent: self.indent,
+            };
+            printer.statement(statement);
+            let multiline = printer.out.contains('\n');
+
+            if let Some((previous, previous_multiline)) = previous {
+                if previous_multiline
+                    || multiline
+                    || std::mem::discriminant(previous) != std::mem::discriminant(statement)
+                {
+                    self.out.push('\n');
+                }
+                self.newline();
+            }
+            self.push(&printer.out);
+            previous = Some((statement, multiline));
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        if block.body.is_empty() {
+            self.push("{}");
+            return;
+        }
+        self.push("{");
+        self.indent += 1;
+        self.newline();
+        self.statements(&block.body);
+        self.indent -= 1;
+        self.newline();
+        self.push("}");
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Variable(assignment) => self.variable_assignment(assignment),
+            Statement::Member(assignment) => self.member_assignment(assignment),
+            Statement::Expression(statement) => self.expression(&statement.expression),
+            Statement::Return(statement) => {
+                self.push("return ");
+                self.expression(&statement.argument);
+            }
+            Statement::Option(statement) => {
+                self.push("option ");
+                match &statement.assignment {
+                    Assignment::Variable(assignment) => self.variable_assignment(assignment),
+                    Assignment::Member(assignment) => self.member_assignment(assignment),
+                }
+            }
+            Statement::Builtin(statement) => {
+                self.push("builtin ");
+                self.push(&statement.id.name);
+                if let Some(ty) = &statement.ty {
+                    self.push(" : ");
+                    self.type_expression(ty);
+                }
+            }
+            Statement::Test(statement) => {
+                self.push("test ");
+                self.variable_assignment(&statement.assignment);
+            }
+            Statement::TestCase(statement) => {
+                self.push("testcase ");
+                self.push(&statement.id.name);
+                if let Some(extends) = &statement.extends {
+                    self.push(" extends ");
+                    self.string(&extends.value);
+                }
+                self.push(" ");
+                self.block(&statement.block);
+            }
+            Statement::Bad(statement) => self.push(&statement.text),
+        }
+    }
+
+    fn variable_assignment(&mut self, assignment: &VariableAssignment) {
+        self.push(&assignment.id.name);
+        self.push(" = ");
+        self.expression(&assignment.init);
+    }
+
+    fn member_assignment(&mut self, assignment: &MemberAssignment) {
+        self.member(&assignment.member);
+        self.push(" = ");
+        self.expression(&assignment.init);
+    }
+
+    // Writes an operand, in parentheses if it binds more loosely than `max`.
+    fn operand(&mut self, expression: &Expression, max: u8) {
+        if precedence(expression) > max {
+            self.push("(");
+            self.expression(expression);
+            self.push(")");
+        } else {
+            self.expression(expression);
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        let precedence = precedence(expression);
+        match expression {
+            Expression::Identifier(id) => self.push(&id.name),
+            Expression::Array(array) => {
+                self.push("[");
+                self.list(&array.elements, Self::expression);
+                self.push("]");
+            }
+            Expression::Dict(dict) => {
+                if dict.elements.is_empty() {
+                    self.push("[:]");
+                } else {
+                    self.push("[");
+                    self.list(&dict.elements, |p, item| {
+                        p.expression(&item.key);
+                        p.push(": ");
+                        p.expression(&item.val);
+                    });
+                    self.push("]");
+                }
+            }
+            Expression::Function(function) => self.function(function),
+            Expression::Logical(logical) => {
+                self.operand(&logical.left, precedence);
+                self.push(" ");
+                self.push(&logical.operator.to_string());
+                self.push(" ");
+                self.operand(&logical.right, precedence - 1);
+            }
+            Expression::Object(object) => {
+                self.push("{");
+                self.object_body(object);
+                self.push("}");
+            }
+            Expression::Member(member) => self.member(member),
+            Expression::Index(index) => {
+                self.operand(&index.array, 1);
+                self.push("[");
+                self.expression(&index.index);
+                self.push("]");
+            }
+            Expression::Binary(binary) => {
+                self.operand(&binary.left, precedence);
+                self.push(" ");
+                self.push(&binary.operator.to_string());
+                self.push(" ");
+                self.operand(&binary.right, precedence - 1);
+            }
+            Expression::Unary(unary) => {
+                self.push(&unary.operator.to_string());
+                if !matches!(unary.operator, Operator::Addition | Operator::Subtraction) {
+                    self.push(" ");
+                }
+                self.operand(&unary.argument, precedence);
+            }
+            Expression::Pipe(pipe) => {
+                self.operand(&pipe.argument, precedence);
+                self.indent += 1;
+                self.newline();
+                self.push("|> ");
+                self.call(&pipe.call);
+                self.indent -= 1;
+            }
+            Expression::Call(call) => self.call(call),
+            Expression::Conditional(conditional) => {
+                self.push("if ");
+                self.operand(&conditional.test, precedence);
+                self.push(" then ");
+                self.operand(&conditional.consequent, precedence);
+                self.push(" else ");
+                self.operand(&conditional.alternate, precedence);
+            }
+            Expression::Interpolated(string) => {
+                self.push("\"");
+                for part in &string.parts {
+                    match part {
+                        StringExpressionPart::Text(text) => self.escaped(&text.value),
+                        StringExpressionPart::Interpolated(part) => {
+                            self.push("${");
+                            self.expression(&part.expression);
+                            self.push("}");
+                        }
+                    }
+                }
+                self.push("\"");
+            }
+            Expression::Paren(paren) => {
+                self.push("(");
+                self.expression(&paren.expression);
+                self.push(")");
+            }
+            Expression::Integer(integer) => self.push(&integer.value.to_string()),
+            Expression::UnsignedInteger(integer) => self.push(&integer.value.to_string()),
+            Expression::Float(float) => {
+                if float.value.fract() == 0.0 {
+                    self.push(&format!("{:.1}", float.value));
+                } else {
+                    self.push(&float.value.to_string());
+                }
+            }
+            Expression::String(string) => self.string(&string.value),
+            Expression::Duration(duration) => {
+                for value in &duration.values {
+                    self.push(&value.magnitude.to_string());
+                    self.push(&value.unit);
+                }
+            }
+            Expression::DateTime(date_time) => {
+                self.push(&date_time.value.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+            }
+            Expression::Boolean(boolean) => self.push(&boolean.value.to_string()),
+            Expression::Regexp(regexp) => {
+                self.push("/");
+                self.push(&regexp.value.replace('/', "\\/"));
+                self.push("/");
+            }
+            Expression::PipeLiteral(_) => self.push("<-"),
+            Expression::Label(label) => {
+                self.push(".");
+                self.push(&label.value);
+            }
+            Expression::Bad(bad) => self.push(&bad.text),
+        }
+    }
+
+    fn function(&mut self, function: &FunctionExpression) {
+        self.push("(");
+        self.list(&function.params, |p, param| p.property(param, "="));
+        self.push(") => ");
+        match &function.body {
+            FunctionBody::Block(block) => self.block(block),
+            // A body starting with `{` would be read as a block
+            FunctionBody::Expression(body) => match **body {
+                Expression::Object(_) => {
+                    self.push("(");
+                    self.expression(body);
+                    self.push(")");
+                }
+                _ => self.expression(body),
+            },
+        }
+    }
+
+    fn call(&mut self, call: &CallExpression) {
+        self.operand(&call.callee, 1);
+        self.push("(");
+        match call.arguments.as_slice() {
+            [Expression::Object(arguments)] => self.object_body(arguments),
+            arguments => self.list(arguments, Self::expression),
+        }
+        self.push(")");
+    }
+
+    fn member(&mut self, member: &MemberExpression) {
+        self.operand(&member.object, 1);
+        match &member.property {
+            PropertyKey::Identifier(id) => {
+                self.push(".");
+                self.push(&id.name);
+            }
+            PropertyKey::String(s) => {
+                self.push("[");
+                self.string(&s.value);
+                self.push("]");
+            }
+        }
+    }
+
+    fn object_body(&mut self, object: &ObjectExpression) {
+        if let Some(with) = &object.with {
+            self.push(&with.name);
+            self.push(" with ");
+        }
+        self.list(&object.properties, |p, property| p.property(property, ": "));
+    }
+
+    fn property(&mut self, property: &Property, separator: &str) {
+        self.property_key(&property.key);
+        if let Some(value) = &property.value {
+            self.push(separator);
+            self.expression(value);
+        }
+    }
+
+    fn property_key(&mut self, key: &PropertyKey) {
+        match key {
+            PropertyKey::Identifier(id) => self.push(&id.name),
+            PropertyKey::String(s) => self.string(&s.value),
+        }
+    }
+
+    fn string(&mut self, s: &str) {
+        self.push("\"");
+        self.escaped(s);
+        self.push("\"");
+    }
+
+    fn escaped(&mut self, s: &str) {
+        // Writing to a `String` can't fail
+        let _ = write_escaped(&mut self.out, s);
+    }
+
+    // Type expressions of builtins are kept as JSON, see `BuiltinStatement`.
+    fn type_expression(&mut self, ty: &Value) {
+        match ty["type"].as_str().unwrap_or_default() {
+            "TypeExpression" => {
+                self.type_expression(&ty["monotype"]);
+                let constraints = ty["constraints"].as_array().map_or(&[][..], Vec::as_slice);
+                if !constraints.is_empty() {
+                    self.push(" where ");
+                    self.list(constraints, |p, constraint| {
+                        p.type_name(&constraint["tvar"]);
+                        p.push(": ");
+                        let kinds = constraint["kinds"]
+                            .as_array()
+                            .map_or(&[][..], Vec::as_slice);
+                        for (i, kind) in kinds.iter().enumerate() {
+                            if i > 0 {
+                                p.push(" + ");
+                            }
+                            p.type_name(kind);
+                        }
+                    });
+                }
+            }
+            "NamedType" | "TvarType" => self.type_name(&ty["name"]),
+            "ArrayType" => {
+                self.push("[");
+                self.type_expression(&ty["element"]);
+                self.push("]");
+            }
+            "StreamType" => {
+                self.push("stream[");
+                self.type_expression(&ty["element"]);
+                self.push("]");
+            }
+            "DictType" => {
+                self.push("[");
+                self.type_expression(&ty["key"]);
+                self.push(": ");
+                self.type_expression(&ty["val"]);
+                self.push("]");
+            }
+            "RecordType" => {
+                self.push("{");
+                if !ty["tvar"].is_null() {
+                    self.type_name(&ty["tvar"]);
+                    self.push(" with ");
+                }
+                let properties = ty["properties"].as_array().map_or(&[][..], Vec::as_slice);
+                self.list(properties, |p, property| {
+                    p.type_name(&property["name"]);
+                    p.push(": ");
+                    p.type_expression(&property["monotype"]);
+                });
+                self.push("}");
+            }
+            "FunctionType" => {
+                self.push("(");
+                let parameters = ty["parameters"].as_array().map_or(&[][..], Vec::as_slice);
+                self.list(parameters, |p, parameter| {
+                    match parameter["type"].as_str() {
+                        Some("Pipe") => p.push("<-"),
+                        Some("Optional") => p.push("?"),
+                        _ => {}
+                    }
+                    if !parameter["name"].is_null() {
+                        p.type_name(&parameter["name"]);
+                    }
+                    p.push(": ");
+                    p.type_expression(&parameter["monotype"]);
+                });
+                self.push(") => ");
+                self.type_expression(&ty["monotype"]);
+            }
+            _ => {}
+        }
+    }
+
+    // The name of an `Identifier`, or the value of a `StringLiteral`
+    fn type_name(&mut self, name: &Value) {
+        match name["type"].as_str() {
+            Some("StringLiteral") => self.string(name["value"].as_str().unwrap_or_default()),
+            _ => self.push(name["name"].as_str().unwrap_or_default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ast::{BinaryExpression, Identifier, IntegerLiteral, UnaryExpression};
+
+    fn int(value: i64) -> Expression {
+        Expression::Integer(IntegerLiteral { value })
+    }
+
+    fn binary(operator: Operator, left: Expression, right: Expression) -> Expression {
+        Expression::Binary(BinaryExpression {
+            operator,
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+    }
+
+    #[test]
+    fn adds_parentheses_for_precedence() {
+        let sum = binary(Operator::Addition, int(1), int(2));
+        let product = binary(Operator::Multiplication, sum.clone(), int(3));
+        assert_eq!(product.to_string(), "(1 + 2) * 3");
+
+        let left = binary(Operator::Subtraction, sum.clone(), int(3));
+        assert_eq!(left.to_string(), "1 + 2 - 3");
+        let right = binary(Operator::Subtraction, int(3), sum);
+        assert_eq!(right.to_string(), "3 - (1 + 2)");
+
+        let negated = Expression::Unary(UnaryExpression {
+            operator: Operator::Not,
+            argument: Box::new(Expression::Identifier(Identifier::new("a"))),
+        });
+        assert_eq!(negated.to_string(), "not a");
+    }
+
+    #[test]
+    fn formats_builtin_types() {
+        let statement: Statement = serde_json::from_str(
+            r#"{
+                "type": "BuiltinStatement",
+                "id": {"type": "Identifier", "name": "sum"},
+                "ty": {
+                    "type": "TypeExpression",
+                    "monotype": {
+                        "type": "FunctionType",
+                        "parameters": [
+                            {
+                                "type": "Pipe",
+                                "name": {"type": "Identifier", "name": "tables"},
+                                "monotype": {
+                                    "type": "StreamType",
+                                    "element": {"type": "TvarType", "name": {"type": "Identifier", "name": "A"}}
+                                }
+                            },
+                            {
+                                "type": "Optional",
+                                "name": {"type": "Identifier", "name": "column"},
+                                "monotype": {"type": "NamedType", "name": {"type": "Identifier", "name": "string"}}
+                            }
+                        ],
+                        "monotype": {
+                            "type": "StreamType",
+                            "element": {"type": "TvarType", "name": {"type": "Identifier", "name": "A"}}
+                        }
+                    },
+                    "constraints": [{
+                        "type": "TypeConstraint",
+                        "tvar": {"type": "Identifier", "name": "A"},
+                        "kinds": [{"type": "Identifier", "name": "Record"}]
+                    }]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            statement.to_string(),
+            "builtin sum : (<-tables: stream[A], ?column: string) => stream[A] where A: Record"
+        );
+    }
+}
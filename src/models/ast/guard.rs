@@ -0,0 +1,208 @@
+//! Checks and rewrites of queries before they are submitted
+//!
+//! Combined with `Client::query_ast`, these let a service look at the
+//! scripts its users send: which buckets they read or write, whether every
+//! scan is bounded by a `range()`, and cap the number of rows returned.
+
+use std::collections::BTreeSet;
+
+use crate::models::ast::visit::{
+    walk_call_expression, walk_call_expression_mut, walk_expression, walk_expression_mut,
+    Visitor, VisitorMut,
+};
+use crate::models::ast::{
+    CallExpression, Expression, Identifier, IntegerLiteral, ObjectExpression, Package,
+    PipeExpression, PipeLiteral, Property, PropertyKey, Statement,
+};
+
+/// The buckets named in `from()` and `to()` calls.
+///
+/// Only buckets given as a string literal are found, a bucket held in a
+/// variable or built at runtime is not.
+pub fn referenced_buckets(package: &Package) -> BTreeSet<String> {
+    struct Buckets(BTreeSet<String>);
+
+    impl<'ast> Visitor<'ast> for Buckets {
+        fn visit_call_expression(&mut self, call: &'ast CallExpression) {
+            if matches!(callee_name(call), Some("from") | Some("to")) {
+                if let Some(Expression::String(bucket)) = argument(call, "bucket") {
+                    self.0.insert(bucket.value.clone());
+                }
+            }
+            walk_call_expression(self, call);
+        }
+    }
+
+    let mut buckets = Buckets(BTreeSet::new());
+    buckets.visit_package(package);
+    buckets.0
+}
+
+/// Whether every `from()` is piped into a `range()`.
+///
+/// Each pipe chain is checked on its own: a `from()` assigned to a variable
+/// that is later piped into `range()` counts as unbounded.
+pub fn has_range_call(package: &Package) -> bool {
+    from_chains(package)
+        .iter()
+        .all(|(_, calls)| calls.iter().any(|call| callee_name(call) == Some("range")))
+}
+
+/// Limits the tables of each result to `n` rows.
+///
+/// `|> limit(n: n)` is added before every `yield()`, wherever it is: in an
+/// assignment, a function body or a block. Every expression statement that
+/// yields a stream, whether a call, a pipe or a variable holding one, is
+/// limited too. A `limit()` already in that place is lowered to `n` instead.
+pub fn inject_limit(package: &mut Package, n: i64) {
+    struct Yields(i64);
+
+    impl VisitorMut for Yields {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            walk_expression_mut(self, expression);
+            if let Expression::Pipe(pipe) = expression {
+                if callee_name(&pipe.call) == Some("yield") {
+                    limit(&mut pipe.argument, self.0);
+                }
+            }
+        }
+
+        // `yield(tables: t)`, without a pipe
+        fn visit_call_expression_mut(&mut self, call: &mut CallExpression) {
+            walk_call_expression_mut(self, call);
+            if callee_name(call) == Some("yield") {
+                if let Some(tables) = argument_mut(call, "tables") {
+                    limit(tables, self.0);
+                }
+            }
+        }
+    }
+
+    Yields(n).visit_package_mut(package);
+    for file in &mut package.files {
+        for statement in &mut file.body {
+            if let Statement::Expression(statement) = statement {
+                limit(&mut statement.expression, n);
+            }
+        }
+    }
+}
+
+fn limit(expression: &mut Expression, n: i64) {
+    if let Expression::Pipe(pipe) = expression {
+        match callee_name(&pipe.call) {
+            Some("yield") => return limit(&mut pipe.argument, n),
+            Some("limit") => {
+                if let Some(Expression::Integer(limit)) = argument_mut(&mut pipe.call, "n") {
+                    limit.value = limit.value.min(n);
+                    return;
+                }
+            }
+            _ => {}
+        }
+    }
+    if !matches!(
+        expression,
+        Expression::Pipe(_)
+            | Expression::Call(_)
+            | Expression::Identifier(_)
+            | Expression::Member(_)
+            | Expression::Index(_)
+    ) {
+        return;
+    }
+
+    let call = CallExpression::new(
+        Expression::Identifier(Identifier::new("limit")),
+        vec![Expression::Object(ObjectExpression {
+            with: None,
+            properties: vec![Property::new(
+                PropertyKey::Identifier(Identifier::new("n")),
+                Some(Expression::Integer(IntegerLiteral { value: n })),
+            )],
+        })],
+    );
+    let argument = std::mem::replace(expression, Expression::PipeLiteral(PipeLiteral {}));
+    *expression = Expression::Pipe(PipeExpression {
+        argument: Box::new(argument),
+        call,
+    });
+}
+
+// Each `from()` with the calls it is piped into
+pub(super) fn from_chains(package: &Package) -> Vec<(&CallExpression, Vec<&CallExpression>)> {
+    #[derive(Default)]
+    struct Chains<'ast>(Vec<(&'ast CallExpression, Vec<&'ast CallExpression>)>);
+
+    impl<'ast> Visitor<'ast> for Chains<'ast> {
+        fn visit_expression(&mut self, expression: &'ast Expression) {
+            if !matches!(expression, Expression::Pipe(_) | Expression::Call(_)) {
+                return walk_expression(self, expression);
+            }
+
+            // Visit the arguments of the whole chain, but not its inner pipes
+            let (source, calls) = pipe_chain(expression);
+            if let Expression::Call(source) = source {
+                walk_call_expression(self, source);
+            } else {
+                self.visit_expression(source);
+            }
+            for call in &calls {
+                walk_call_expression(self, call);
+            }
+
+            if let Expression::Call(source) = source {
+                if callee_name(source) == Some("from") {
+                    self.0.push((source, calls));
+                }
+            }
+        }
+    }
+
+    let mut chains = Chains::default();
+    chains.visit_package(package);
+    chains.0
+}
+
+// Splits `a |> f() |> g()` into `a` and `[f(), g()]`.
+fn pipe_chain(expression: &Expression) -> (&Expression, Vec<&CallExpression>) {
+    let mut calls = Vec::new();
+    let mut source = expression;
+    while let Expression::Pipe(pipe) = source {
+        calls.push(&pipe.call);
+        source = &pipe.argument;
+    }
+    calls.reverse();
+    (source, calls)
+}
+
+// `f` for `f()` and `pkg.f()`
+pub(super) fn callee_name(call: &CallExpression) -> Option<&str> {
+    match &*call.callee {
+        Expression::Identifier(id) => Some(&id.name),
+        Expression::Member(member) => Some(member.property.name()),
+        _ => None,
+    }
+}
+
+pub(super) fn argument<'a>(call: &'a CallExpression, name: &str) -> Option<&'a Expression> {
+    match call.arguments.first() {
+        Some(Expression::Object(arguments)) => arguments
+            .properties
+            .iter()
+            .find(|property| property.key.name() == name)
+            .and_then(|property| property.value.as_ref()),
+        _ => None,
+    }
+}
+
+fn argument_mut<'a>(call: &'a mut CallExpression, name: &str) -> Option<&'a mut Expression> {
+    match call.arguments.first_mut() {
+        Some(Expression::Object(arguments)) => arguments
+            .properties
+            .iter_mut()
+            .find(|property| property.key.name() == name)
+            .and_then(|property| property.value.as_mut()),
+        _ => None,
+    }
+}
@@ -2,23 +2,25 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::ast::{Identifier, StringLiteral};
+
 /// Declares a package import
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct ImportDeclaration {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// Import Identifier
     #[serde(rename = "as", skip_serializing_if = "Option::is_none")]
-    pub r#as: Option<crate::models::ast::Identifier>,
+    pub r#as: Option<Identifier>,
     /// Import Path
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub path: Option<crate::models::ast::StringLiteral>,
+    pub path: StringLiteral,
 }
 
 impl ImportDeclaration {
     /// Declares a package import
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            r#as: None,
+            path: StringLiteral::new(path),
+        }
     }
 }
@@ -0,0 +1,142 @@
+//! Linting of Flux scripts
+//!
+//! `lint` checks a parsed script for mistakes the server doesn't report until
+//! the query runs, if at all. `Client::lint_query` adds the errors found by
+//! `query_analyze` to these.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::ast::guard::{argument, callee_name, from_chains};
+use crate::models::ast::visit::{walk_call_expression, Visitor};
+use crate::models::ast::{CallExpression, Expression, Package, Position, SourceLocation};
+use crate::models::AnalyzeQueryResponseErrors;
+
+/// A problem found in a script
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// The check that found it
+    pub rule: Rule,
+    /// Description of the problem
+    pub message: String,
+    /// Where the problem is, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<SourceLocation>,
+}
+
+/// How serious a `Diagnostic` is
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The query fails
+    Error,
+    /// The query runs, but likely not as intended
+    Warning,
+    /// The query could be improved
+    Info,
+}
+
+/// The check behind a `Diagnostic`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+    /// An error reported by the server's analysis of the script
+    Analysis,
+    /// `from()` isn't piped into `range()`, so the whole bucket is read
+    UnboundedRange,
+    /// `from()` isn't piped into `filter()`, so every series is read
+    MissingFilter,
+    /// Two `yield()` calls use the same name
+    DuplicateYield,
+}
+
+impl From<&AnalyzeQueryResponseErrors> for Diagnostic {
+    fn from(error: &AnalyzeQueryResponseErrors) -> Self {
+        let location = match (error.line, error.column) {
+            (Some(line), Some(column)) => Some(SourceLocation::at(Position::new(
+                line.max(0) as u32,
+                column.max(0) as u32,
+            ))),
+            _ => None,
+        };
+        Self {
+            severity: Severity::Error,
+            rule: Rule::Analysis,
+            message: error.message.clone().unwrap_or_default(),
+            location,
+        }
+    }
+}
+
+/// Checks a script for unbounded scans, unfiltered scans and yield names used
+/// twice. Diagnostics are sorted by location.
+pub fn lint(package: &Package) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for (from, calls) in from_chains(package) {
+        let calls_any = |name| calls.iter().any(|call| callee_name(call) == Some(name));
+        if !calls_any("range") {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                rule: Rule::UnboundedRange,
+                message: "from() is not piped into range(), so the whole bucket is read"
+                    .to_string(),
+                location: from.location,
+            });
+        }
+        if !calls_any("filter") {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Info,
+                rule: Rule::MissingFilter,
+                message: "from() is not piped into filter(), so every series is read".to_string(),
+                location: from.location,
+            });
+        }
+    }
+
+    let mut yields = Yields::default();
+    yields.visit_package(package);
+    let mut seen: HashMap<&str, Option<SourceLocation>> = HashMap::new();
+    for (name, call) in yields.0 {
+        match seen.get(name) {
+            Some(first) => {
+                let first = match first {
+                    Some(location) => format!(" on line {}", location.start.line),
+                    None => String::new(),
+                };
+                diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    rule: Rule::DuplicateYield,
+                    message: format!("yield name `{}` is already used{}", name, first),
+                    location: call.location,
+                });
+            }
+            None => {
+                seen.insert(name, call.location);
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.location.map(|location| location.start));
+    diagnostics
+}
+
+// Each `yield()` call with its result name
+#[derive(Default)]
+struct Yields<'ast>(Vec<(&'ast str, &'ast CallExpression)>);
+
+impl<'ast> Visitor<'ast> for Yields<'ast> {
+    fn visit_call_expression(&mut self, call: &'ast CallExpression) {
+        if callee_name(call) == Some("yield") {
+            match argument(call, "name") {
+                Some(Expression::String(name)) => self.0.push((&name.value, call)),
+                // Names that aren't literals can't be compared
+                Some(_) => {}
+                None => self.0.push(("_result", call)),
+            }
+        }
+        walk_call_expression(self, call);
+    }
+}
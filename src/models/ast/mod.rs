@@ -1,11 +1,39 @@
 //! Query AST models
+//!
+//! The abstract syntax tree of a Flux query, as returned by
+//! `Client::query_ast`. Every node is serialized with its kind in a `type`
+//! field, which the `Expression`, `Statement` and other enums dispatch on.
+//! Source locations are only kept for calls.
+
+// Serialize each variant as its node, which writes its own `type` field.
+macro_rules! serialize_variants {
+    ($name:ident { $($variant:ident),* $(,)? }) => {
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    $(Self::$variant(node) => node.serialize(serializer),)*
+                }
+            }
+        }
+    };
+}
 
 pub mod identifier;
 pub use self::identifier::Identifier;
 pub mod statement;
-pub use self::statement::Statement;
+pub use self::statement::{
+    Assignment, BadStatement, Block, BuiltinStatement, ExpressionStatement, MemberAssignment,
+    OptionStatement, ReturnStatement, Statement, TestCaseStatement, TestStatement,
+};
 pub mod expression;
-pub use self::expression::Expression;
+pub use self::expression::{
+    ArrayExpression, BadExpression, BinaryExpression, BooleanLiteral, ConditionalExpression,
+    DateTimeLiteral, DictExpression, DurationLiteral, Expression, FloatLiteral, FunctionBody,
+    FunctionExpression, IndexExpression, IntegerLiteral, InterpolatedPart, LabelLiteral,
+    LogicalExpression, LogicalOperator, ObjectExpression, Operator, ParenExpression,
+    PipeExpression, PipeLiteral, RegexpLiteral, StringExpression, StringExpressionPart, TextPart,
+    UnaryExpression, UnsignedIntegerLiteral,
+};
 pub mod call_expression;
 pub use self::call_expression::CallExpression;
 pub mod member_expression;
@@ -16,8 +44,6 @@ pub mod dict_item;
 pub use self::dict_item::DictItem;
 pub mod variable_assignment;
 pub use self::variable_assignment::VariableAssignment;
-pub mod node;
-pub use self::node::Node;
 pub mod property;
 pub use self::property::Property;
 pub mod property_key;
@@ -32,3 +58,12 @@ pub mod package_clause;
 pub use self::package_clause::PackageClause;
 pub mod duration;
 pub use self::duration::Duration;
+pub mod source_location;
+pub use self::source_location::{Position, SourceLocation};
+mod format;
+pub mod visit;
+pub use self::visit::{Visitor, VisitorMut};
+pub mod guard;
+pub use self::guard::{has_range_call, inject_limit, referenced_buckets};
+pub mod lint;
+pub use self::lint::{lint, Diagnostic, Rule, Severity};
@@ -5,10 +5,8 @@ use serde::{Deserialize, Serialize};
 
 /// Represents a complete package source tree.
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct Package {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// Package import path
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
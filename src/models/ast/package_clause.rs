@@ -2,20 +2,21 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::ast::Identifier;
+
 /// Defines a package identifier
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct PackageClause {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// Package name
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub name: Option<crate::models::ast::Identifier>,
+    pub name: Identifier,
 }
 
 impl PackageClause {
     /// Defines a package identifier
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: Identifier::new(name),
+        }
     }
 }
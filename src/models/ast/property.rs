@@ -2,23 +2,23 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::ast::{Expression, PropertyKey};
+
 /// The value associated with a key
-#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct Property {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// Property Key
+    pub key: PropertyKey,
+    /// Property Value, missing for a shorthand property such as `{a}` or a
+    /// function parameter without a default
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub key: Option<crate::models::ast::PropertyKey>,
-    /// Property Value
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub value: Option<crate::models::ast::Expression>,
+    pub value: Option<Expression>,
 }
 
 impl Property {
     /// The value associated with a key
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(key: PropertyKey, value: Option<Expression>) -> Self {
+        Self { key, value }
     }
 }
@@ -1,24 +1,31 @@
 //! PropertyKey
 
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-/// Key value pair
-#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
-pub struct PropertyKey {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
-    /// PropertyKey name
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub name: Option<String>,
-    /// PropertyKey value
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+use crate::models::ast::{Identifier, StringLiteral};
+
+/// Key of a property, or the property of a member expression
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum PropertyKey {
+    /// `a` in `{a: 1}` or `r.a`
+    Identifier(Identifier),
+    /// `"a"` in `{"a": 1}` or `r["a"]`
+    #[serde(rename = "StringLiteral")]
+    String(StringLiteral),
 }
 
+serialize_variants!(PropertyKey {
+    Identifier,
+    String,
+});
+
 impl PropertyKey {
-    /// Returns an instance of PropertyKey
-    pub fn new() -> Self {
-        Self::default()
+    /// The key, whether it is an identifier or a string
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Identifier(id) => &id.name,
+            Self::String(s) => &s.value,
+        }
     }
 }
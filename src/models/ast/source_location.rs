@@ -0,0 +1,40 @@
+//! SourceLocation
+
+use serde::{Deserialize, Serialize};
+
+/// The span of a node in the source of a script
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// Where the node starts
+    pub start: Position,
+    /// Where the node ends, just past its last character
+    pub end: Position,
+}
+
+impl SourceLocation {
+    /// A span covering a single position
+    pub fn at(position: Position) -> Self {
+        Self {
+            start: position,
+            end: position,
+        }
+    }
+}
+
+/// A position in the source of a script, both counted from 1
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub struct Position {
+    /// Line number
+    pub line: u32,
+    /// Column number
+    pub column: u32,
+}
+
+impl Position {
+    /// A position in the source of a script
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+}
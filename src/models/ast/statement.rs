@@ -2,38 +2,149 @@
 
 use serde::{Deserialize, Serialize};
 
-/// Expression Statement
-#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
-pub struct Statement {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
-    /// Raw source text
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub text: Option<String>,
-    /// Statement identitfier
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<crate::models::ast::Identifier>,
-    /// Initial Value
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub init: Option<crate::models::ast::Expression>,
-    /// Member
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub member: Option<crate::models::ast::MemberExpression>,
-    /// Expression
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub expression: Option<crate::models::ast::Expression>,
-    /// Argument
+use crate::models::ast::{Expression, Identifier, MemberExpression, StringLiteral, VariableAssignment};
+
+/// A Flux statement
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum Statement {
+    /// `a = 1`
+    #[serde(rename = "VariableAssignment")]
+    Variable(VariableAssignment),
+    /// `a.b = 1`
+    #[serde(rename = "MemberAssignment")]
+    Member(MemberAssignment),
+    /// An expression on its own, e.g. `from(bucket: "b") |> yield()`
+    #[serde(rename = "ExpressionStatement")]
+    Expression(ExpressionStatement),
+    /// `return a`
+    #[serde(rename = "ReturnStatement")]
+    Return(ReturnStatement),
+    /// `option now = () => 2021-01-01T00:00:00Z`
+    #[serde(rename = "OptionStatement")]
+    Option(OptionStatement),
+    /// `builtin now : () => time`
+    #[serde(rename = "BuiltinStatement")]
+    Builtin(BuiltinStatement),
+    /// `test a = () => ...`
+    #[serde(rename = "TestStatement")]
+    Test(TestStatement),
+    /// `testcase a { ... }`
+    #[serde(rename = "TestCaseStatement")]
+    TestCase(TestCaseStatement),
+    /// A statement that couldn't be parsed
+    #[serde(rename = "BadStatement")]
+    Bad(BadStatement),
+}
+
+serialize_variants!(Statement {
+    Variable,
+    Member,
+    Expression,
+    Return,
+    Option,
+    Builtin,
+    Test,
+    TestCase,
+    Bad,
+});
+
+/// Represents an assignment to a member of an object
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MemberAssignment {
+    /// The member assigned to
+    pub member: MemberExpression,
+    /// The value assigned
+    pub init: Expression,
+}
+
+/// An expression used as a statement
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ExpressionStatement {
+    /// The expression
+    pub expression: Expression,
+}
+
+/// Returns a value from a block
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ReturnStatement {
+    /// The returned value
+    pub argument: Expression,
+}
+
+/// Sets an option
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct OptionStatement {
+    /// The option assignment
+    pub assignment: Assignment,
+}
+
+/// The assignment of an option
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum Assignment {
+    /// `option a = 1`
+    #[serde(rename = "VariableAssignment")]
+    Variable(VariableAssignment),
+    /// `option a.b = 1`
+    #[serde(rename = "MemberAssignment")]
+    Member(MemberAssignment),
+}
+
+serialize_variants!(Assignment {
+    Variable,
+    Member,
+});
+
+/// Declares a builtin value and its type
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct BuiltinStatement {
+    /// Name of the builtin
+    pub id: Identifier,
+    /// The type expression, kept as JSON
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub argument: Option<crate::models::ast::Expression>,
-    /// Assignment
+    pub ty: Option<serde_json::Value>,
+}
+
+/// Declares a test
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct TestStatement {
+    /// The test assignment
+    pub assignment: VariableAssignment,
+}
+
+/// Declares a test case
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct TestCaseStatement {
+    /// Name of the test case
+    pub id: Identifier,
+    /// The test case extended by this one
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub assignment: Option<crate::models::ast::VariableAssignment>,
+    pub extends: Option<StringLiteral>,
+    /// Body of the test case
+    pub block: Block,
 }
 
-impl Statement {
-    /// Returns an instance of Statement
-    pub fn new() -> Self {
-        Self::default()
-    }
+/// A statement that couldn't be parsed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct BadStatement {
+    /// Raw source text
+    pub text: String,
+}
+
+/// A list of statements, e.g. the body of a function
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct Block {
+    /// Statements of the block
+    #[serde(default)]
+    pub body: Vec<Statement>,
 }
@@ -2,23 +2,24 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::ast::{Expression, Identifier};
+
 /// Represents the declaration of a variable
-#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct VariableAssignment {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// Variable Identifier
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<crate::models::ast::Identifier>,
+    pub id: Identifier,
     /// Variable initial value
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub init: Option<crate::models::ast::Expression>,
+    pub init: Expression,
 }
 
 impl VariableAssignment {
     /// Represents the declaration of a variable
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(id: impl Into<String>, init: Expression) -> Self {
+        Self {
+            id: Identifier::new(id),
+            init,
+        }
     }
 }
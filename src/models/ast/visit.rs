@@ -0,0 +1,391 @@
+//! Traversal of the AST
+//!
+//! `Visitor` walks the tree by reference and `VisitorMut` by mutable
+//! reference. Each method defaults to the matching `walk_*` function, which
+//! visits the children of the node, so an implementation only overrides the
+//! nodes it is interested in and calls `walk_*` itself to keep descending.
+//!
+//! Identifiers are visited where they are used as values: the names bound by
+//! assignments and parameters, property keys and member properties are not.
+
+use crate::models::ast::{
+    Assignment, Block, CallExpression, Expression, FunctionBody, Identifier, Package, Property,
+    Statement, StringExpressionPart,
+};
+use crate::models::File;
+
+/// Walks the AST by reference
+pub trait Visitor<'ast> {
+    /// Visits a package
+    fn visit_package(&mut self, package: &'ast Package) {
+        walk_package(self, package);
+    }
+
+    /// Visits a file
+    fn visit_file(&mut self, file: &'ast File) {
+        walk_file(self, file);
+    }
+
+    /// Visits a statement
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        walk_statement(self, statement);
+    }
+
+    /// Visits a block, such as the body of a function
+    fn visit_block(&mut self, block: &'ast Block) {
+        walk_block(self, block);
+    }
+
+    /// Visits an expression
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        walk_expression(self, expression);
+    }
+
+    /// Visits a call, including the calls of pipe expressions
+    fn visit_call_expression(&mut self, call: &'ast CallExpression) {
+        walk_call_expression(self, call);
+    }
+
+    /// Visits a property of an object, or a function parameter
+    fn visit_property(&mut self, property: &'ast Property) {
+        walk_property(self, property);
+    }
+
+    /// Visits an identifier used as a value
+    fn visit_identifier(&mut self, _identifier: &'ast Identifier) {}
+}
+
+/// Visits the files of a package
+pub fn walk_package<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, package: &'ast Package) {
+    for file in &package.files {
+        visitor.visit_file(file);
+    }
+}
+
+/// Visits the statements of a file
+pub fn walk_file<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, file: &'ast File) {
+    for statement in &file.body {
+        visitor.visit_statement(statement);
+    }
+}
+
+/// Visits the expressions and blocks of a statement
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    statement: &'ast Statement,
+) {
+    match statement {
+        Statement::Variable(assignment) => visitor.visit_expression(&assignment.init),
+        Statement::Member(assignment) => {
+            visitor.visit_expression(&assignment.member.object);
+            visitor.visit_expression(&assignment.init);
+        }
+        Statement::Expression(statement) => visitor.visit_expression(&statement.expression),
+        Statement::Return(statement) => visitor.visit_expression(&statement.argument),
+        Statement::Option(statement) => match &statement.assignment {
+            Assignment::Variable(assignment) => visitor.visit_expression(&assignment.init),
+            Assignment::Member(assignment) => {
+                visitor.visit_expression(&assignment.member.object);
+                visitor.visit_expression(&assignment.init);
+            }
+        },
+        Statement::Test(statement) => visitor.visit_expression(&statement.assignment.init),
+        Statement::TestCase(statement) => visitor.visit_block(&statement.block),
+        Statement::Builtin(_) | Statement::Bad(_) => {}
+    }
+}
+
+/// Visits the statements of a block
+pub fn walk_block<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, block: &'ast Block) {
+    for statement in &block.body {
+        visitor.visit_statement(statement);
+    }
+}
+
+/// Visits the children of an expression
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    expression: &'ast Expression,
+) {
+    match expression {
+        Expression::Identifier(id) => visitor.visit_identifier(id),
+        Expression::Array(array) => {
+            for element in &array.elements {
+                visitor.visit_expression(element);
+            }
+        }
+        Expression::Dict(dict) => {
+            for item in &dict.elements {
+                visitor.visit_expression(&item.key);
+                visitor.visit_expression(&item.val);
+            }
+        }
+        Expression::Function(function) => {
+            for param in &function.params {
+                visitor.visit_property(param);
+            }
+            match &function.body {
+                FunctionBody::Block(block) => visitor.visit_block(block),
+                FunctionBody::Expression(body) => visitor.visit_expression(body),
+            }
+        }
+        Expression::Logical(logical) => {
+            visitor.visit_expression(&logical.left);
+            visitor.visit_expression(&logical.right);
+        }
+        Expression::Object(object) => {
+            if let Some(with) = &object.with {
+                visitor.visit_identifier(with);
+            }
+            for property in &object.properties {
+                visitor.visit_property(property);
+            }
+        }
+        Expression::Member(member) => visitor.visit_expression(&member.object),
+        Expression::Index(index) => {
+            visitor.visit_expression(&index.array);
+            visitor.visit_expression(&index.index);
+        }
+        Expression::Binary(binary) => {
+            visitor.visit_expression(&binary.left);
+            visitor.visit_expression(&binary.right);
+        }
+        Expression::Unary(unary) => visitor.visit_expression(&unary.argument),
+        Expression::Pipe(pipe) => {
+            visitor.visit_expression(&pipe.argument);
+            visitor.visit_call_expression(&pipe.call);
+        }
+        Expression::Call(call) => visitor.visit_call_expression(call),
+        Expression::Conditional(conditional) => {
+            visitor.visit_expression(&conditional.test);
+            visitor.visit_expression(&conditional.consequent);
+            visitor.visit_expression(&conditional.alternate);
+        }
+        Expression::Interpolated(string) => {
+            for part in &string.parts {
+                if let StringExpressionPart::Interpolated(part) = part {
+                    visitor.visit_expression(&part.expression);
+                }
+            }
+        }
+        Expression::Paren(paren) => visitor.visit_expression(&paren.expression),
+        Expression::Bad(bad) => {
+            if let Some(expression) = &bad.expression {
+                visitor.visit_expression(expression);
+            }
+        }
+        Expression::Integer(_)
+        | Expression::UnsignedInteger(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Duration(_)
+        | Expression::DateTime(_)
+        | Expression::Boolean(_)
+        | Expression::Regexp(_)
+        | Expression::PipeLiteral(_)
+        | Expression::Label(_) => {}
+    }
+}
+
+/// Visits the callee and the arguments of a call
+pub fn walk_call_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    call: &'ast CallExpression,
+) {
+    visitor.visit_expression(&call.callee);
+    for argument in &call.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+/// Visits the value of a property
+pub fn walk_property<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, property: &'ast Property) {
+    if let Some(value) = &property.value {
+        visitor.visit_expression(value);
+    }
+}
+
+/// Walks the AST by mutable reference
+pub trait VisitorMut {
+    /// Visits a package
+    fn visit_package_mut(&mut self, package: &mut Package) {
+        walk_package_mut(self, package);
+    }
+
+    /// Visits a file
+    fn visit_file_mut(&mut self, file: &mut File) {
+        walk_file_mut(self, file);
+    }
+
+    /// Visits a statement
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    /// Visits a block, such as the body of a function
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    /// Visits an expression
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    /// Visits a call, including the calls of pipe expressions
+    fn visit_call_expression_mut(&mut self, call: &mut CallExpression) {
+        walk_call_expression_mut(self, call);
+    }
+
+    /// Visits a property of an object, or a function parameter
+    fn visit_property_mut(&mut self, property: &mut Property) {
+        walk_property_mut(self, property);
+    }
+
+    /// Visits an identifier used as a value
+    fn visit_identifier_mut(&mut self, _identifier: &mut Identifier) {}
+}
+
+/// Visits the files of a package
+pub fn walk_package_mut<V: VisitorMut + ?Sized>(visitor: &mut V, package: &mut Package) {
+    for file in &mut package.files {
+        visitor.visit_file_mut(file);
+    }
+}
+
+/// Visits the statements of a file
+pub fn walk_file_mut<V: VisitorMut + ?Sized>(visitor: &mut V, file: &mut File) {
+    for statement in &mut file.body {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+/// Visits the expressions and blocks of a statement
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Variable(assignment) => visitor.visit_expression_mut(&mut assignment.init),
+        Statement::Member(assignment) => {
+            visitor.visit_expression_mut(&mut assignment.member.object);
+            visitor.visit_expression_mut(&mut assignment.init);
+        }
+        Statement::Expression(statement) => visitor.visit_expression_mut(&mut statement.expression),
+        Statement::Return(statement) => visitor.visit_expression_mut(&mut statement.argument),
+        Statement::Option(statement) => match &mut statement.assignment {
+            Assignment::Variable(assignment) => visitor.visit_expression_mut(&mut assignment.init),
+            Assignment::Member(assignment) => {
+                visitor.visit_expression_mut(&mut assignment.member.object);
+                visitor.visit_expression_mut(&mut assignment.init);
+            }
+        },
+        Statement::Test(statement) => visitor.visit_expression_mut(&mut statement.assignment.init),
+        Statement::TestCase(statement) => visitor.visit_block_mut(&mut statement.block),
+        Statement::Builtin(_) | Statement::Bad(_) => {}
+    }
+}
+
+/// Visits the statements of a block
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for statement in &mut block.body {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+/// Visits the children of an expression
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::Identifier(id) => visitor.visit_identifier_mut(id),
+        Expression::Array(array) => {
+            for element in &mut array.elements {
+                visitor.visit_expression_mut(element);
+            }
+        }
+        Expression::Dict(dict) => {
+            for item in &mut dict.elements {
+                visitor.visit_expression_mut(&mut item.key);
+                visitor.visit_expression_mut(&mut item.val);
+            }
+        }
+        Expression::Function(function) => {
+            for param in &mut function.params {
+                visitor.visit_property_mut(param);
+            }
+            match &mut function.body {
+                FunctionBody::Block(block) => visitor.visit_block_mut(block),
+                FunctionBody::Expression(body) => visitor.visit_expression_mut(body),
+            }
+        }
+        Expression::Logical(logical) => {
+            visitor.visit_expression_mut(&mut logical.left);
+            visitor.visit_expression_mut(&mut logical.right);
+        }
+        Expression::Object(object) => {
+            if let Some(with) = &mut object.with {
+                visitor.visit_identifier_mut(with);
+            }
+            for property in &mut object.properties {
+                visitor.visit_property_mut(property);
+            }
+        }
+        Expression::Member(member) => visitor.visit_expression_mut(&mut member.object),
+        Expression::Index(index) => {
+            visitor.visit_expression_mut(&mut index.array);
+            visitor.visit_expression_mut(&mut index.index);
+        }
+        Expression::Binary(binary) => {
+            visitor.visit_expression_mut(&mut binary.left);
+            visitor.visit_expression_mut(&mut binary.right);
+        }
+        Expression::Unary(unary) => visitor.visit_expression_mut(&mut unary.argument),
+        Expression::Pipe(pipe) => {
+            visitor.visit_expression_mut(&mut pipe.argument);
+            visitor.visit_call_expression_mut(&mut pipe.call);
+        }
+        Expression::Call(call) => visitor.visit_call_expression_mut(call),
+        Expression::Conditional(conditional) => {
+            visitor.visit_expression_mut(&mut conditional.test);
+            visitor.visit_expression_mut(&mut conditional.consequent);
+            visitor.visit_expression_mut(&mut conditional.alternate);
+        }
+        Expression::Interpolated(string) => {
+            for part in &mut string.parts {
+                if let StringExpressionPart::Interpolated(part) = part {
+                    visitor.visit_expression_mut(&mut part.expression);
+                }
+            }
+        }
+        Expression::Paren(paren) => visitor.visit_expression_mut(&mut paren.expression),
+        Expression::Bad(bad) => {
+            if let Some(expression) = &mut bad.expression {
+                visitor.visit_expression_mut(expression);
+            }
+        }
+        Expression::Integer(_)
+        | Expression::UnsignedInteger(_)
+        | Expression::Float(_)
+        | Expression::String(_)
+        | Expression::Duration(_)
+        | Expression::DateTime(_)
+        | Expression::Boolean(_)
+        | Expression::Regexp(_)
+        | Expression::PipeLiteral(_)
+        | Expression::Label(_) => {}
+    }
+}
+
+/// Visits the callee and the arguments of a call
+pub fn walk_call_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    call: &mut CallExpression,
+) {
+    visitor.visit_expression_mut(&mut call.callee);
+    for argument in &mut call.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+/// Visits the value of a property
+pub fn walk_property_mut<V: VisitorMut + ?Sized>(visitor: &mut V, property: &mut Property) {
+    if let Some(value) = &mut property.value {
+        visitor.visit_expression_mut(value);
+    }
+}
@@ -106,6 +106,26 @@ impl DataPoint {
     pub fn builder(measurement: impl Into<String>) -> DataPointBuilder {
         DataPointBuilder::new(measurement)
     }
+
+    /// The measurement name.
+    pub fn measurement(&self) -> &str {
+        &self.measurement
+    }
+
+    /// The tags, sorted by key.
+    pub fn tags(&self) -> &BTreeMap<String, String> {
+        &self.tags
+    }
+
+    /// The fields, sorted by key.
+    pub fn fields(&self) -> &BTreeMap<String, FieldValue> {
+        &self.fields
+    }
+
+    /// The timestamp, if one was set.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
 }
 
 impl WriteDataPoint for DataPoint {
@@ -143,7 +163,10 @@ impl WriteDataPoint for DataPoint {
 }
 
 /// Possible value types
+///
+/// More types may be added, so matches need a wildcard arm.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum FieldValue {
     /// A true or false value
     Bool(bool),
@@ -151,10 +174,40 @@ pub enum FieldValue {
     F64(f64),
     /// A 64-bit signed integer number
     I64(i64),
+    /// A 64-bit unsigned integer number
+    U64(u64),
     /// A string value
     String(String),
 }
 
+impl FieldValue {
+    /// The type of this value.
+    pub fn field_type(&self) -> FieldType {
+        match self {
+            Self::Bool(_) => FieldType::Bool,
+            Self::F64(_) => FieldType::F64,
+            Self::I64(_) => FieldType::I64,
+            Self::U64(_) => FieldType::U64,
+            Self::String(_) => FieldType::String,
+        }
+    }
+}
+
+/// Possible field types, as stored by InfluxDB
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum FieldType {
+    /// A true or false value
+    Bool,
+    /// A 64-bit floating point number
+    F64,
+    /// A 64-bit signed integer number
+    I64,
+    /// A 64-bit unsigned integer number
+    U64,
+    /// A string value
+    String,
+}
+
 impl From<bool> for FieldValue {
     fn from(other: bool) -> Self {
         Self::Bool(other)
@@ -173,6 +226,12 @@ impl From<i64> for FieldValue {
     }
 }
 
+impl From<u64> for FieldValue {
+    fn from(other: u64) -> Self {
+        Self::U64(other)
+    }
+}
+
 impl From<&str> for FieldValue {
     fn from(other: &str) -> Self {
         Self::String(other.into())
@@ -221,7 +280,7 @@ impl WriteMeasurement for str {
     }
 }
 
-trait WriteTagKey {
+pub(crate) trait WriteTagKey {
     fn write_tag_key_to<W>(&self, w: W) -> io::Result<()>
     where
         W: io::Write;
@@ -236,7 +295,7 @@ impl WriteTagKey for str {
     }
 }
 
-trait WriteTagValue {
+pub(crate) trait WriteTagValue {
     fn write_tag_value_to<W>(&self, w: W) -> io::Result<()>
     where
         W: io::Write;
@@ -283,6 +342,7 @@ impl WriteFieldValue for FieldValue {
             Bool(v) => write!(w, "{}", if *v { "t" } else { "f" }),
             F64(v) => write!(w, "{}", v),
             I64(v) => write!(w, "{}i", v),
+            U64(v) => write!(w, "{}u", v),
             String(v) => {
                 w.write_all(br#"""#)?;
                 escape_and_write_value(v, FIELD_VALUE_STRING_DELIMITERS, &mut w)?;
@@ -307,13 +367,13 @@ impl WriteTimestamp for i64 {
     }
 }
 
-const MEASUREMENT_DELIMITERS: &[char] = &[',', ' '];
-const TAG_KEY_DELIMITERS: &[char] = &[',', '=', ' '];
-const TAG_VALUE_DELIMITERS: &[char] = TAG_KEY_DELIMITERS;
-const FIELD_KEY_DELIMITERS: &[char] = TAG_KEY_DELIMITERS;
-const FIELD_VALUE_STRING_DELIMITERS: &[char] = &['"'];
+pub(crate) const MEASUREMENT_DELIMITERS: &[char] = &[',', ' '];
+pub(crate) const TAG_KEY_DELIMITERS: &[char] = &[',', '=', ' '];
+pub(crate) const TAG_VALUE_DELIMITERS: &[char] = TAG_KEY_DELIMITERS;
+pub(crate) const FIELD_KEY_DELIMITERS: &[char] = TAG_KEY_DELIMITERS;
+pub(crate) const FIELD_VALUE_STRING_DELIMITERS: &[char] = &['"', '\\'];
 
-fn escape_and_write_value<W>(
+pub(crate) fn escape_and_write_value<W>(
     value: &str,
     escaping_specification: &[char],
     mut w: W,
@@ -329,7 +389,7 @@ where
         last = idx + delim.len();
     }
 
-    w.write_all(value[last..].as_bytes())
+    w.write_all(&value.as_bytes()[last..])
 }
 
 #[cfg(test)]
@@ -444,6 +504,16 @@ mod tests {
         );
     }
 
+    #[test]
+    fn backslashes_are_escaped_in_field_values_of_strings() {
+        assert_utf8_strings_eq(
+            &FieldValue::from(r#"C:\temp\"quoted""#)
+                .field_value_to_vec()
+                .unwrap(),
+            br#""C:\\temp\\\"quoted\"""#.as_ref(),
+        );
+    }
+
     #[test]
     fn field_value_of_bool() {
         let e = FieldValue::from(true);
@@ -465,6 +535,12 @@ mod tests {
         assert_utf8_strings_eq(&e.field_value_to_vec().unwrap(), b"42i");
     }
 
+    #[test]
+    fn field_value_of_unsigned_integer() {
+        let e = FieldValue::from(42_u64);
+        assert_utf8_strings_eq(&e.field_value_to_vec().unwrap(), b"42u");
+    }
+
     #[test]
     fn field_value_of_string() {
         let e = FieldValue::from("hello");
@@ -4,10 +4,8 @@ use serde::{Deserialize, Serialize};
 
 /// Represents a source from a single file
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub struct File {
-    /// Type of AST node
-    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
-    pub r#type: Option<String>,
     /// The name of the file.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -18,7 +16,7 @@ pub struct File {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub imports: Vec<crate::models::ast::ImportDeclaration>,
     /// List of Flux statements
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
     pub body: Vec<crate::models::ast::Statement>,
 }
 
@@ -0,0 +1,92 @@
+//! Measurement Schema
+
+use serde::{Deserialize, Serialize};
+
+/// Explicit schema of a measurement in a bucket with the `explicit` schema
+/// type
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeasurementSchema {
+    /// Measurement Schema ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Organization ID of the bucket
+    #[serde(rename = "orgID", skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+    /// Bucket ID
+    #[serde(rename = "bucketID", skip_serializing_if = "Option::is_none")]
+    pub bucket_id: Option<String>,
+    /// Measurement name
+    pub name: String,
+    /// Ordered collection of column definitions
+    #[serde(default)]
+    pub columns: Vec<MeasurementSchemaColumn>,
+    /// Created At
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// Updated At
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+impl MeasurementSchema {
+    /// Returns instance of MeasurementSchema
+    pub fn new(name: String, columns: Vec<MeasurementSchemaColumn>) -> Self {
+        Self {
+            name,
+            columns,
+            ..Default::default()
+        }
+    }
+}
+
+/// Definition of a measurement column
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeasurementSchemaColumn {
+    /// Column name
+    pub name: String,
+    /// Semantic type of the column
+    #[serde(rename = "type")]
+    pub r#type: ColumnSemanticType,
+    /// Data type of a field column
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<ColumnDataType>,
+}
+
+/// Semantic type of a measurement column
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnSemanticType {
+    /// Timestamp
+    Timestamp,
+    /// Tag
+    Tag,
+    /// Field
+    Field,
+}
+
+/// Data type of a field column
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnDataType {
+    /// Integer
+    Integer,
+    /// Float
+    Float,
+    /// Boolean
+    Boolean,
+    /// String
+    String,
+    /// Unsigned
+    Unsigned,
+}
+
+/// List of measurement schemas
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeasurementSchemaList {
+    /// Measurement schemas
+    #[serde(default)]
+    pub measurement_schemas: Vec<MeasurementSchema>,
+}
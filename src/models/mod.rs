@@ -27,13 +27,17 @@ pub use self::retention_rule::RetentionRule;
 pub mod query;
 pub use self::query::{
     AnalyzeQueryResponse, AnalyzeQueryResponseErrors, AstResponse, FluxSuggestion, FluxSuggestions,
-    LanguageRequest, Query,
+    LanguageRequest, ParamValue, Query,
 };
 pub mod file;
 pub use self::file::File;
 pub mod health;
 pub use self::health::{HealthCheck, Status};
 pub mod data_point;
-pub use data_point::{DataPoint, FieldValue, WriteDataPoint};
+pub use data_point::{DataPoint, FieldType, FieldValue, WriteDataPoint};
+pub mod measurement_schema;
+pub use measurement_schema::{MeasurementSchema, MeasurementSchemaColumn, MeasurementSchemaList};
+pub mod write_schema;
+pub use write_schema::{MeasurementWriteSchema, SchemaError, WriteSchema};
 pub mod task;
 pub use task::{Task, TaskStatusType, Tasks};
@@ -1,13 +1,16 @@
 //! Query
 
+use crate::api::cancel::CancellationToken;
 use crate::models::ast::dialect::Annotations;
 use crate::models::ast::Package;
 use crate::models::File;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
 /// Query influx using the Flux language
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Query {
     /// Query Script
     #[serde(rename = "extern", skip_serializing_if = "Option::is_none")]
@@ -24,6 +27,26 @@ pub struct Query {
     /// Default is the server's now time.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub now: Option<String>,
+    /// Parameters referenced as `params.name` in the query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<BTreeMap<String, ParamValue>>,
+    // Client-side options, set with `with_timeout` and `with_cancellation`
+    #[serde(skip)]
+    timeout: Option<Duration>,
+    #[serde(skip)]
+    cancellation: Option<CancellationToken>,
+}
+
+// Queries are equal when they send the same request
+impl PartialEq for Query {
+    fn eq(&self, other: &Self) -> bool {
+        self.r#extern == other.r#extern
+            && self.query == other.query
+            && self.r#type == other.r#type
+            && self.dialect == other.dialect
+            && self.now == other.now
+            && self.params == other.params
+    }
 }
 
 impl Query {
@@ -34,6 +57,166 @@ impl Query {
             ..Default::default()
         }
     }
+
+    /// Add a parameter, referenced as `params.name` in the query. Values
+    /// are sent apart from the query, so they never need escaping.
+    ///
+    /// ```
+    /// use influxdb2::models::Query;
+    ///
+    /// let query = Query::new(
+    ///     r#"from(bucket: params.bucket) |> range(start: duration(v: params.since))"#.into(),
+    /// )
+    /// .with_param("bucket", r#"my "bucket""#)
+    /// .with_param("since", chrono::Duration::hours(-1));
+    /// ```
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<ParamValue>) -> Self {
+        self.params
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Fail the query with a timeout error if it doesn't complete within
+    /// `timeout`, reading the response included.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Let `token` cancel the query, see `CancellationToken`.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+}
+
+/// Value of a query parameter
+///
+/// Flux has no JSON representation of times and durations, so they are sent
+/// as strings, to convert with `time(v: params.name)` and
+/// `duration(v: params.name)` in the query.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    /// A string
+    String(String),
+    /// An integer
+    Int(i64),
+    /// An unsigned integer
+    UInt(u64),
+    /// A float
+    Float(f64),
+    /// A boolean
+    Bool(bool),
+    /// A point in time, sent as an RFC 3339 string
+    Time(DateTime<Utc>),
+    /// A duration, sent as a duration literal such as `-1h30m`
+    Duration(chrono::Duration),
+}
+
+impl Serialize for ParamValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::String(v) => serializer.serialize_str(v),
+            Self::Int(v) => serializer.serialize_i64(*v),
+            Self::UInt(v) => serializer.serialize_u64(*v),
+            Self::Float(v) => serializer.serialize_f64(*v),
+            Self::Bool(v) => serializer.serialize_bool(*v),
+            Self::Time(v) => {
+                serializer.serialize_str(&v.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            Self::Duration(v) => {
+                let literal = crate::flux::Literal::Duration(*v).to_string();
+                serializer.serialize_str(&literal)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ParamValue {
+    // Times and durations are strings once serialized, so they come back as
+    // strings.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Json {
+            Bool(bool),
+            Int(i64),
+            UInt(u64),
+            Float(f64),
+            String(String),
+        }
+
+        Ok(match Json::deserialize(deserializer)? {
+            Json::Bool(v) => Self::Bool(v),
+            Json::Int(v) => Self::Int(v),
+            Json::UInt(v) => Self::UInt(v),
+            Json::Float(v) => Self::Float(v),
+            Json::String(v) => Self::String(v),
+        })
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(v: &str) -> Self {
+        Self::String(v.to_string())
+    }
+}
+
+impl From<String> for ParamValue {
+    fn from(v: String) -> Self {
+        Self::String(v)
+    }
+}
+
+impl From<i64> for ParamValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<i32> for ParamValue {
+    fn from(v: i32) -> Self {
+        Self::Int(v.into())
+    }
+}
+
+impl From<u64> for ParamValue {
+    fn from(v: u64) -> Self {
+        Self::UInt(v)
+    }
+}
+
+impl From<f64> for ParamValue {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<bool> for ParamValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for ParamValue {
+    fn from(v: DateTime<Tz>) -> Self {
+        Self::Time(v.with_timezone(&Utc))
+    }
+}
+
+impl From<chrono::Duration> for ParamValue {
+    fn from(v: chrono::Duration) -> Self {
+        Self::Duration(v)
+    }
 }
 
 impl Default for Query {
@@ -51,6 +234,9 @@ impl Default for Query {
                 ..Default::default()
             }),
             now: None,
+            params: None,
+            timeout: None,
+            cancellation: None,
         }
     }
 }
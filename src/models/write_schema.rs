@@ -0,0 +1,332 @@
+//! Client-side write schema
+//!
+//! Describes the tags and fields allowed for each measurement so that points
+//! can be checked before they are sent, instead of having the server reject
+//! the whole batch.
+
+use snafu::{ensure, OptionExt, Snafu};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::models::measurement_schema::{
+    ColumnDataType, ColumnSemanticType, MeasurementSchema, MeasurementSchemaList,
+};
+use crate::models::{DataPoint, FieldType};
+
+/// Errors returned when a `DataPoint` doesn't match a `WriteSchema`
+#[derive(Debug, Clone, PartialEq, Snafu)]
+pub enum SchemaError {
+    /// The measurement is not described by the schema
+    #[snafu(display("Measurement `{}` is not in the schema", measurement))]
+    UnknownMeasurement {
+        /// The measurement name
+        measurement: String,
+    },
+    /// The tag key is not allowed for the measurement
+    #[snafu(display("Tag `{}` is not allowed for measurement `{}`", tag, measurement))]
+    UnknownTag {
+        /// The measurement name
+        measurement: String,
+        /// The tag key
+        tag: String,
+    },
+    /// The field is not allowed for the measurement
+    #[snafu(display("Field `{}` is not allowed for measurement `{}`", field, measurement))]
+    UnknownField {
+        /// The measurement name
+        measurement: String,
+        /// The field name
+        field: String,
+    },
+    /// The field value has a different type than the one in the schema
+    #[snafu(display(
+        "Field `{}` of measurement `{}` must be {:?}, got {:?}",
+        field,
+        measurement,
+        expected,
+        found
+    ))]
+    FieldTypeMismatch {
+        /// The measurement name
+        measurement: String,
+        /// The field name
+        field: String,
+        /// The type declared in the schema
+        expected: FieldType,
+        /// The type of the value in the point
+        found: FieldType,
+    },
+}
+
+/// Allowed tags and fields of a single measurement
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeasurementWriteSchema {
+    tags: BTreeSet<String>,
+    // `None` accepts a value of any type
+    fields: BTreeMap<String, Option<FieldType>>,
+}
+
+impl MeasurementWriteSchema {
+    /// Create a schema allowing no tags or fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow the tag `key`.
+    pub fn tag(mut self, key: impl Into<String>) -> Self {
+        self.tags.insert(key.into());
+        self
+    }
+
+    /// Allow the field `name` with values of type `field_type`.
+    pub fn field(mut self, name: impl Into<String>, field_type: FieldType) -> Self {
+        self.fields.insert(name.into(), Some(field_type));
+        self
+    }
+
+    /// Allow the field `name` with values of any type.
+    pub fn untyped_field(mut self, name: impl Into<String>) -> Self {
+        self.fields.insert(name.into(), None);
+        self
+    }
+
+    /// Allowed tag keys.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.iter().map(String::as_str)
+    }
+
+    /// Allowed fields and their type, `None` if any type is accepted.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, Option<FieldType>)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), *v))
+    }
+}
+
+impl From<&MeasurementSchema> for MeasurementWriteSchema {
+    fn from(schema: &MeasurementSchema) -> Self {
+        let mut res = Self::new();
+        for column in &schema.columns {
+            match column.r#type {
+                ColumnSemanticType::Tag => res = res.tag(&column.name),
+                ColumnSemanticType::Field => match column.data_type {
+                    Some(data_type) => res = res.field(&column.name, data_type.into()),
+                    None => res = res.untyped_field(&column.name),
+                },
+                ColumnSemanticType::Timestamp => {}
+            }
+        }
+        res
+    }
+}
+
+impl From<ColumnDataType> for FieldType {
+    fn from(data_type: ColumnDataType) -> Self {
+        match data_type {
+            ColumnDataType::Integer => Self::I64,
+            ColumnDataType::Float => Self::F64,
+            ColumnDataType::Boolean => Self::Bool,
+            ColumnDataType::String => Self::String,
+            ColumnDataType::Unsigned => Self::U64,
+        }
+    }
+}
+
+/// Allowed tags and fields of every measurement in a bucket.
+///
+/// # Example
+///
+/// ```
+/// use influxdb2::models::{DataPoint, FieldType, MeasurementWriteSchema, WriteSchema};
+///
+/// let schema = WriteSchema::new().measurement(
+///     "cpu",
+///     MeasurementWriteSchema::new()
+///         .tag("host")
+///         .field("usage", FieldType::F64),
+/// );
+///
+/// let point = DataPoint::builder("cpu")
+///     .tag("host", "server01")
+///     .field("usage", 1_i64)
+///     .build()
+///     .unwrap();
+/// assert!(schema.validate(&point).is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriteSchema {
+    measurements: BTreeMap<String, MeasurementWriteSchema>,
+}
+
+impl WriteSchema {
+    /// Create a schema allowing no measurements.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the schema of `name`, replacing any existing one.
+    pub fn measurement(mut self, name: impl Into<String>, schema: MeasurementWriteSchema) -> Self {
+        self.measurements.insert(name.into(), schema);
+        self
+    }
+
+    /// Get the schema of the measurement `name`.
+    pub fn get(&self, name: &str) -> Option<&MeasurementWriteSchema> {
+        self.measurements.get(name)
+    }
+
+    /// Check that the measurement, tags and fields of `point` are allowed by
+    /// the schema.
+    pub fn validate(&self, point: &DataPoint) -> Result<(), SchemaError> {
+        let measurement = point.measurement();
+        let schema = self
+            .measurements
+            .get(measurement)
+            .context(UnknownMeasurement { measurement })?;
+
+        for tag in point.tags().keys() {
+            ensure!(schema.tags.contains(tag), UnknownTag { measurement, tag });
+        }
+
+        for (field, value) in point.fields() {
+            let expected = schema
+                .fields
+                .get(field)
+                .context(UnknownField { measurement, field })?;
+            if let Some(expected) = *expected {
+                let found = value.field_type();
+                ensure!(
+                    expected == found,
+                    FieldTypeMismatch {
+                        measurement,
+                        field,
+                        expected,
+                        found
+                    }
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&MeasurementSchemaList> for WriteSchema {
+    fn from(list: &MeasurementSchemaList) -> Self {
+        list.measurement_schemas
+            .iter()
+            .fold(Self::new(), |schema, measurement| {
+                schema.measurement(&measurement.name, measurement.into())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::measurement_schema::MeasurementSchemaColumn;
+
+    fn schema() -> WriteSchema {
+        WriteSchema::new().measurement(
+            "cpu",
+            MeasurementWriteSchema::new()
+                .tag("host")
+                .field("usage", FieldType::F64)
+                .untyped_field("note"),
+        )
+    }
+
+    #[test]
+    fn valid_point() {
+        let point = DataPoint::builder("cpu")
+            .tag("host", "server01")
+            .field("usage", 0.5)
+            .field("note", true)
+            .build()
+            .unwrap();
+        assert_eq!(schema().validate(&point), Ok(()));
+    }
+
+    #[test]
+    fn invalid_points() {
+        let point = DataPoint::builder("mem")
+            .field("used", 1.0)
+            .build()
+            .unwrap();
+        assert_eq!(
+            schema().validate(&point),
+            Err(SchemaError::UnknownMeasurement {
+                measurement: "mem".to_string()
+            })
+        );
+
+        let point = DataPoint::builder("cpu")
+            .tag("region", "us-west")
+            .field("usage", 0.5)
+            .build()
+            .unwrap();
+        assert_eq!(
+            schema().validate(&point),
+            Err(SchemaError::UnknownTag {
+                measurement: "cpu".to_string(),
+                tag: "region".to_string(),
+            })
+        );
+
+        let point = DataPoint::builder("cpu")
+            .field("idle", 0.5)
+            .build()
+            .unwrap();
+        assert_eq!(
+            schema().validate(&point),
+            Err(SchemaError::UnknownField {
+                measurement: "cpu".to_string(),
+                field: "idle".to_string(),
+            })
+        );
+
+        let point = DataPoint::builder("cpu")
+            .field("usage", 1_i64)
+            .build()
+            .unwrap();
+        assert_eq!(
+            schema().validate(&point),
+            Err(SchemaError::FieldTypeMismatch {
+                measurement: "cpu".to_string(),
+                field: "usage".to_string(),
+                expected: FieldType::F64,
+                found: FieldType::I64,
+            })
+        );
+    }
+
+    #[test]
+    fn from_explicit_bucket_schema() {
+        let list: MeasurementSchemaList = serde_json::from_str(
+            r#"{"measurementSchemas": [{
+                "id": "1", "orgID": "2", "bucketID": "3", "name": "cpu",
+                "columns": [
+                    {"name": "time", "type": "timestamp"},
+                    {"name": "host", "type": "tag"},
+                    {"name": "usage", "type": "field", "dataType": "float"},
+                    {"name": "count", "type": "field", "dataType": "unsigned"}
+                ]
+            }]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            list.measurement_schemas[0].columns[0],
+            MeasurementSchemaColumn {
+                name: "time".to_string(),
+                r#type: ColumnSemanticType::Timestamp,
+                data_type: None,
+            }
+        );
+
+        let expected = WriteSchema::new().measurement(
+            "cpu",
+            MeasurementWriteSchema::new()
+                .tag("host")
+                .field("usage", FieldType::F64)
+                .field("count", FieldType::U64),
+        );
+        assert_eq!(WriteSchema::from(&list), expected);
+    }
+}
@@ -3,7 +3,31 @@
 //! Trying to construct the trait used for line protocol
 //! https://docs.influxdata.com/influxdb/v2.6/reference/syntax/line-protocol/#Copyright
 
+use chrono::{DateTime, TimeZone};
 use influxdb2_derive::{impl_tuple_fields, impl_tuple_tags};
+use serde::Serialize;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::write::TimestampPrecision;
+use crate::models::data_point::{
+    escape_and_write_value, FIELD_KEY_DELIMITERS, FIELD_VALUE_STRING_DELIMITERS,
+    MEASUREMENT_DELIMITERS,
+};
+
+// Escape using the same rules as `DataPoint`, so that both produce the same
+// line protocol.
+fn escape(value: &str, escaping_specification: &[char]) -> String {
+    let mut buf = Vec::with_capacity(value.len());
+    escape_and_write_value(value, escaping_specification, &mut buf)
+        .expect("writing to a Vec never fails");
+    String::from_utf8(buf).expect("escaping keeps the value valid UTF-8")
+}
+
+/// Escape a measurement name for line protocol
+pub fn encode_measurement(measurement: &str) -> String {
+    escape(measurement, MEASUREMENT_DELIMITERS)
+}
 
 /// InfluxDB WritableValue trait
 ///
@@ -14,20 +38,10 @@ use influxdb2_derive::{impl_tuple_fields, impl_tuple_tags};
 pub trait ValueWritable {
     /// encode_value into influxdb support string
     fn encode_value(&self) -> String;
-}
 
-struct Value<T: ValueWritable> {
-    inner: T,
-}
-
-impl<T: ValueWritable> From<T> for Value<T> {
-    fn from(value: T) -> Self {
-        Self { inner: value }
-    }
-}
-impl<T: ValueWritable> ValueWritable for Value<T> {
-    fn encode_value(&self) -> String {
-        self.inner.encode_value()
+    /// encode_value, or `None` if the field should be left out of the point
+    fn encode_optional_value(&self) -> Option<String> {
+        Some(self.encode_value())
     }
 }
 
@@ -51,13 +65,13 @@ impl ValueWritable for u64 {
 
 impl ValueWritable for String {
     fn encode_value(&self) -> String {
-        format!("\"{}\"", self)
+        self.as_str().encode_value()
     }
 }
 
 impl ValueWritable for &str {
     fn encode_value(&self) -> String {
-        format!("\"{}\"", self)
+        format!("\"{}\"", escape(self, FIELD_VALUE_STRING_DELIMITERS))
     }
 }
 
@@ -80,6 +94,10 @@ impl<T: ValueWritable> ValueWritable for Option<T> {
             None => "\"None\"".to_string(),
         }
     }
+
+    fn encode_optional_value(&self) -> Option<String> {
+        self.as_ref().and_then(ValueWritable::encode_optional_value)
+    }
 }
 
 /// InfluxDB Key
@@ -92,33 +110,24 @@ impl<T: ValueWritable> ValueWritable for Option<T> {
 pub trait KeyWritable {
     /// encode key as string
     fn encode_key(&self) -> String;
-}
 
-struct Key<T: KeyWritable> {
-    inner: T,
-}
-
-impl<T: KeyWritable> From<T> for Key<T> {
-    fn from(value: T) -> Self {
-        Self { inner: value }
-    }
-}
-
-impl<T: KeyWritable> KeyWritable for Key<T> {
-    fn encode_key(&self) -> String {
-        self.inner.encode_key()
+    /// encode key as string, or `None` if the tag should be left out of the
+    /// point
+    fn encode_optional_key(&self) -> Option<String> {
+        Some(self.encode_key())
     }
 }
 
+// Tag keys, tag values and field keys all escape the same delimiters.
 impl KeyWritable for &str {
     fn encode_key(&self) -> String {
-        self.to_string()
+        escape(self, FIELD_KEY_DELIMITERS)
     }
 }
 
 impl KeyWritable for String {
     fn encode_key(&self) -> String {
-        self.clone()
+        self.as_str().encode_key()
     }
 }
 
@@ -135,6 +144,10 @@ impl<T: KeyWritable> KeyWritable for Option<T> {
             None => "None".to_string(),
         }
     }
+
+    fn encode_optional_key(&self) -> Option<String> {
+        self.as_ref().and_then(KeyWritable::encode_optional_key)
+    }
 }
 
 /// Write tags as key=value
@@ -161,10 +174,39 @@ impl_tuple_fields!((K1, V1, K2, V2, K3, V3, K4, V4, K5, V5));
 impl_tuple_fields!((K1, V1, K2, V2, K3, V3, K4, V4, K5, V5, K6, V6));
 impl_tuple_fields!((K1, V1, K2, V2, K3, V3, K4, V4, K5, V5, K6, V6, K7, V7));
 
+/// Serialize `value` with serde into a tag or field value, e.g. a unit enum
+/// variant into its name. A value serialized as `null` is left out.
+pub fn serde_string<T: Serialize + ?Sized>(value: &T) -> io::Result<Option<String>> {
+    Ok(match serde_json::to_value(value)? {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s),
+        other => Some(other.to_string()),
+    })
+}
+
+/// Tags and fields of a type deriving `WriteDataPoint`
+///
+/// This is what allows a struct to be embedded into another one with
+/// `#[influxdb(flatten)]`.
+pub trait FlattenWritable {
+    /// Append the encoded `(key, value)` of each tag
+    fn encode_tags_into(&self, tags: &mut Vec<(String, String)>) -> io::Result<()>;
+
+    /// Append the encoded `(key, value)` of each field
+    fn encode_fields_into(&self, fields: &mut Vec<(String, String)>) -> io::Result<()>;
+}
+
 /// Any type wants to be a timestamp needs to implement this
 pub trait TimestampWritable {
     /// encode into string like "1465839830100400200"
     fn encode_timestamp(&self) -> String;
+
+    /// encode in `precision`. Integers are assumed to already be in the
+    /// right precision.
+    fn encode_timestamp_with_precision(&self, precision: TimestampPrecision) -> String {
+        let _ = precision;
+        self.encode_timestamp()
+    }
 }
 
 impl TimestampWritable for u64 {
@@ -179,11 +221,40 @@ impl TimestampWritable for i64 {
     }
 }
 
+impl<Tz: TimeZone> TimestampWritable for DateTime<Tz> {
+    fn encode_timestamp(&self) -> String {
+        self.encode_timestamp_with_precision(TimestampPrecision::Nanoseconds)
+    }
+
+    fn encode_timestamp_with_precision(&self, precision: TimestampPrecision) -> String {
+        let nanos =
+            i128::from(self.timestamp()) * 1_000_000_000 + i128::from(self.timestamp_subsec_nanos());
+        precision.convert_nanos(nanos).to_string()
+    }
+}
+
+impl TimestampWritable for SystemTime {
+    fn encode_timestamp(&self) -> String {
+        self.encode_timestamp_with_precision(TimestampPrecision::Nanoseconds)
+    }
+
+    fn encode_timestamp_with_precision(&self, precision: TimestampPrecision) -> String {
+        let nanos = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_nanos() as i128,
+            Err(before) => -(before.duration().as_nanos() as i128),
+        };
+        precision.convert_nanos(nanos).to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::writable::{FieldsWritable, TagsWritable};
 
-    use super::ValueWritable;
+    use super::{serde_string, KeyWritable, TimestampWritable, ValueWritable};
+    use crate::api::write::TimestampPrecision;
+    use chrono::{TimeZone, Utc};
+    use std::time::{Duration, UNIX_EPOCH};
 
     #[test]
     fn value_writable_f64() {
@@ -197,6 +268,71 @@ mod tests {
         assert_eq!(a.encode_value(), "33i")
     }
 
+    #[test]
+    fn value_writable_string_is_escaped() {
+        let a = r#"say "hi" to C:\temp, ok"#;
+        assert_eq!(a.encode_value(), r#""say \"hi\" to C:\\temp, ok""#);
+        assert_eq!(a.to_string().encode_value(), a.encode_value());
+    }
+
+    #[test]
+    fn key_writable_is_escaped() {
+        let a = "a key,with=delimiters";
+        assert_eq!(a.encode_key(), r#"a\ key\,with\=delimiters"#);
+        assert_eq!(a.to_string().encode_key(), a.encode_key());
+        assert_eq!(("k k", "v=v").encode_tags(), r#"k\ k=v\=v"#);
+        assert_eq!(("f,1", r#"v""#).encode_fields(), r#"f\,1="v\"""#);
+    }
+
+    #[test]
+    fn optional_values_are_omitted() {
+        assert_eq!(Some(3_i64).encode_optional_value(), Some("3i".to_string()));
+        assert_eq!(None::<i64>.encode_optional_value(), None);
+        assert_eq!(Some(None::<i64>).encode_optional_value(), None);
+        assert_eq!(Some("a b").encode_optional_key(), Some(r#"a\ b"#.to_string()));
+        assert_eq!(None::<String>.encode_optional_key(), None);
+        assert_eq!("a".encode_optional_key(), Some("a".to_string()));
+    }
+
+    #[test]
+    fn timestamps_with_precision() {
+        let time = Utc.timestamp_opt(1_671_095_854, 123_456_789).unwrap();
+        assert_eq!(time.encode_timestamp(), "1671095854123456789");
+        assert_eq!(
+            time.encode_timestamp_with_precision(TimestampPrecision::Milliseconds),
+            "1671095854123"
+        );
+        assert_eq!(
+            time.encode_timestamp_with_precision(TimestampPrecision::Seconds),
+            "1671095854"
+        );
+
+        let time = UNIX_EPOCH + Duration::from_micros(1_671_095_854_123_456);
+        assert_eq!(
+            time.encode_timestamp_with_precision(TimestampPrecision::Microseconds),
+            "1671095854123456"
+        );
+        let before_epoch = UNIX_EPOCH - Duration::from_millis(1_500);
+        assert_eq!(
+            before_epoch.encode_timestamp_with_precision(TimestampPrecision::Seconds),
+            "-2"
+        );
+        assert_eq!(5_i64.encode_timestamp_with_precision(TimestampPrecision::Seconds), "5");
+    }
+
+    #[test]
+    fn serde_values() {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "snake_case")]
+        enum Region {
+            UsWest,
+        }
+
+        assert_eq!(serde_string(&Region::UsWest).unwrap(), Some("us_west".to_string()));
+        assert_eq!(serde_string(&3).unwrap(), Some("3".to_string()));
+        assert_eq!(serde_string(&None::<Region>).unwrap(), None);
+    }
+
     #[test]
     fn tags_tuple() {
         let a: (&str, &str) = ("33", "str");
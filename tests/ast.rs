@@ -0,0 +1,307 @@
+//! Runs Flux ASTs captured from `/api/v2/query/ast` through the typed models,
+//! the formatter and the query checks. Each `tests/fixtures/ast/*.json` is the
+//! AST of the `.flux` file of the same name, with source locations stripped.
+
+use influxdb2::models::ast::visit::{walk_expression_mut, VisitorMut};
+use influxdb2::models::ast::{
+    has_range_call, inject_limit, lint, referenced_buckets, Expression, Package, Statement,
+};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ast")
+}
+
+fn fixture(name: &str) -> Result<Value> {
+    Ok(serde_json::from_str(&fs::read_to_string(
+        fixtures().join(name),
+    )?)?)
+}
+
+// The server writes some missing values as `null`, the models leave them out.
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+fn round_trip(name: &str) -> Result<Package> {
+    let mut expected = fixture(name)?;
+    let package: Package = serde_json::from_value(expected.clone())?;
+
+    strip_nulls(&mut expected);
+    assert_eq!(serde_json::to_value(&package)?, expected, "{}", name);
+
+    Ok(package)
+}
+
+#[test]
+fn filter_round_trips() -> Result {
+    let package = round_trip("filter.json")?;
+
+    let body = &package.files[0].body;
+    assert_eq!(body.len(), 1);
+    match &body[0] {
+        Statement::Expression(s) => match &s.expression {
+            Expression::Pipe(p) => match &*p.call.callee {
+                Expression::Identifier(id) => assert_eq!(id.name, "yield"),
+                other => panic!("unexpected callee {:?}", other),
+            },
+            other => panic!("unexpected expression {:?}", other),
+        },
+        other => panic!("unexpected statement {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn statements_round_trip() -> Result {
+    let package = round_trip("statements.json")?;
+
+    let file = &package.files[0];
+    assert_eq!(file.imports.len(), 2);
+    assert_eq!(file.imports[1].r#as.as_ref().unwrap().name, "arr");
+    assert!(matches!(file.body[0], Statement::Option(_)));
+    match &file.body[2] {
+        Statement::Variable(a) => {
+            assert_eq!(a.id.name, "threshold");
+            assert!(matches!(&a.init, Expression::Float(f) if f.value == 1.5));
+        }
+        other => panic!("unexpected statement {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn misc_round_trips() -> Result {
+    let package = round_trip("misc.json")?;
+
+    let file = &package.files[0];
+    assert_eq!(file.package.as_ref().unwrap().name.name, "main");
+    assert!(matches!(file.body.last(), Some(Statement::TestCase(_))));
+
+    Ok(())
+}
+
+#[test]
+fn formats_corpus() -> Result {
+    let mut formatted = 0;
+    for entry in fs::read_dir(fixtures())? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+
+        let package: Package = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        let source = fs::read_to_string(path.with_extension("flux"))?;
+        assert_eq!(package.to_string(), source, "{}", path.display());
+        formatted += 1;
+    }
+    assert_eq!(formatted, 4);
+
+    Ok(())
+}
+
+#[test]
+fn source_locations_are_ignored() -> Result {
+    let json = r#"{
+        "type": "Package",
+        "package": "main",
+        "files": [{
+            "type": "File",
+            "location": {"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 7}},
+            "body": [{
+                "type": "ExpressionStatement",
+                "location": {"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 7}},
+                "expression": {
+                    "type": "IntegerLiteral",
+                    "location": {"start": {"line": 1, "column": 1}, "end": {"line": 1, "column": 7}},
+                    "value": "123456"
+                }
+            }]
+        }]
+    }"#;
+
+    let package: Package = serde_json::from_str(json)?;
+    match &package.files[0].body[0] {
+        Statement::Expression(s) => {
+            assert!(matches!(&s.expression, Expression::Integer(i) if i.value == 123_456))
+        }
+        other => panic!("unexpected statement {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn finds_referenced_buckets() -> Result {
+    let filter: Package = serde_json::from_value(fixture("filter.json")?)?;
+    assert_eq!(
+        referenced_buckets(&filter).into_iter().collect::<Vec<_>>(),
+        ["telegraf"]
+    );
+
+    let statements: Package = serde_json::from_value(fixture("statements.json")?)?;
+    assert_eq!(
+        referenced_buckets(&statements)
+            .into_iter()
+            .collect::<Vec<_>>(),
+        ["b"]
+    );
+
+    Ok(())
+}
+
+// Drops the `range()` calls of pipe chains
+struct RemoveRange;
+
+impl VisitorMut for RemoveRange {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        if let Expression::Pipe(pipe) = expression {
+            if matches!(&*pipe.call.callee, Expression::Identifier(id) if id.name == "range") {
+                *expression = (*pipe.argument).clone();
+            }
+        }
+    }
+}
+
+#[test]
+fn detects_missing_range() -> Result {
+    let mut package: Package = serde_json::from_value(fixture("filter.json")?)?;
+    assert!(has_range_call(&package));
+
+    RemoveRange.visit_package_mut(&mut package);
+    assert!(!has_range_call(&package));
+    assert_eq!(
+        package.to_string(),
+        r#"from(bucket: "telegraf")
+    |> filter(fn: (r) => r._measurement == "cpu" and r["host"] =~ /^server/)
+    |> yield(name: "cpu")
+"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn injects_limit() -> Result {
+    let mut package: Package = serde_json::from_value(fixture("filter.json")?)?;
+
+    inject_limit(&mut package, 100);
+    assert_eq!(
+        package.to_string(),
+        r#"from(bucket: "telegraf")
+    |> range(start: -1h)
+    |> filter(fn: (r) => r._measurement == "cpu" and r["host"] =~ /^server/)
+    |> limit(n: 100)
+    |> yield(name: "cpu")
+"#
+    );
+
+    // An existing limit is lowered rather than added again
+    inject_limit(&mut package, 10);
+    inject_limit(&mut package, 50);
+    assert!(package
+        .to_string()
+        .contains("|> limit(n: 10)\n    |> yield"));
+    assert_eq!(package.to_string().matches("limit(").count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn injects_limit_through_variables() -> Result {
+    let mut package: Package = serde_json::from_value(fixture("variable.json")?)?;
+
+    inject_limit(&mut package, 5);
+    assert_eq!(
+        package.to_string(),
+        r#"data = from(bucket: "b")
+    |> range(start: 0)
+
+data
+    |> limit(n: 5)
+
+data
+    |> limit(n: 5)
+    |> yield()
+"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn injects_limit_into_every_yield() -> Result {
+    // `x = from(bucket: "b") |> yield()`
+    let json = r#"{
+        "type": "Package",
+        "package": "main",
+        "files": [{
+            "type": "File",
+            "body": [{
+                "type": "VariableAssignment",
+                "id": {"type": "Identifier", "name": "x"},
+                "init": {
+                    "type": "PipeExpression",
+                    "argument": {
+                        "type": "CallExpression",
+                        "callee": {"type": "Identifier", "name": "from"},
+                        "arguments": [{
+                            "type": "ObjectExpression",
+                            "properties": [{
+                                "type": "Property",
+                                "key": {"type": "Identifier", "name": "bucket"},
+                                "value": {"type": "StringLiteral", "value": "b"}
+                            }]
+                        }]
+                    },
+                    "call": {
+                        "type": "CallExpression",
+                        "callee": {"type": "Identifier", "name": "yield"},
+                        "arguments": []
+                    }
+                }
+            }]
+        }]
+    }"#;
+    let mut package: Package = serde_json::from_str(json)?;
+
+    inject_limit(&mut package, 5);
+    inject_limit(&mut package, 7);
+    assert_eq!(
+        package.to_string(),
+        r#"x = from(bucket: "b")
+    |> limit(n: 5)
+    |> yield()
+"#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn lints_corpus() -> Result {
+    let filter: Package = serde_json::from_value(fixture("filter.json")?)?;
+    assert_eq!(lint(&filter), []);
+
+    // `from(bucket: "b") |> range() |> label()` never filters
+    let statements: Package = serde_json::from_value(fixture("statements.json")?)?;
+    let rules: Vec<_> = lint(&statements).into_iter().map(|d| d.rule).collect();
+    assert_eq!(rules, [influxdb2::models::ast::Rule::MissingFilter]);
+
+    Ok(())
+}
+
@@ -0,0 +1,72 @@
+//! Queries through a proxy set in the environment, in their own test binary
+//! since the environment is shared by all the tests of a process.
+
+use influxdb2::api::cancel::CancellationToken;
+use influxdb2::models::Query;
+use influxdb2::Client;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+type Result<T = (), E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+// A CONNECT proxy answering the tunnelled request itself, returning the
+// first line of the request made to it
+fn proxy(response: &'static str) -> Result<(String, thread::JoinHandle<String>)> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut connect = String::new();
+        reader.read_line(&mut connect).unwrap();
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .unwrap();
+
+        // The request to the server, then its JSON body
+        let mut length = 0;
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if line.len() <= 2 {
+                break;
+            }
+            let lower = line.to_ascii_lowercase();
+            if let Some(value) = lower.strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        reader.read_exact(&mut vec![0; length]).unwrap();
+        stream.write_all(response.as_bytes()).unwrap();
+        connect
+    });
+    Ok((url, handle))
+}
+
+#[test]
+fn cancellable_query_through_proxy() -> Result {
+    let (proxy_url, proxy) = proxy(
+        "HTTP/1.1 200 OK\r\nContent-Length: 33\r\n\r\n\
+         ,result,table,_value\n,_result,0,1\n",
+    )?;
+    std::env::set_var("HTTP_PROXY", proxy_url);
+
+    let client = Client::new("http://influxdb.invalid:8086", "org", "token");
+    let mut query = Query::default();
+    query.dialect = None;
+    let query = query.with_cancellation(CancellationToken::new());
+    let records = client.query_raw(Some(query))?;
+
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        proxy.join().unwrap(),
+        "CONNECT influxdb.invalid:8086 HTTP/1.1\r\n"
+    );
+
+    Ok(())
+}
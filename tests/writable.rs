@@ -0,0 +1,247 @@
+//! Checks that `#[derive(WriteDataPoint)]` types and `DataPoint`s holding the
+//! same data serialise to identical line protocol.
+
+use chrono::{DateTime, TimeZone, Utc};
+use influxdb2::models::{DataPoint, WriteDataPoint};
+use influxdb2_derive::WriteDataPoint;
+use std::fmt;
+
+#[derive(WriteDataPoint)]
+#[measurement = "disk usage,total"]
+struct DiskUsage {
+    #[influxdb(tag)]
+    device: String,
+    #[influxdb(tag)]
+    path: String,
+    #[influxdb(field)]
+    free: i64,
+    #[influxdb(field)]
+    label: String,
+    #[influxdb(field)]
+    readonly: bool,
+    #[influxdb(field)]
+    used: f64,
+    #[influxdb(timestamp)]
+    time: i64,
+}
+
+fn to_line<T: WriteDataPoint>(point: &T) -> String {
+    let mut buf = Vec::new();
+    point.write_data_point_to(&mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+fn assert_same_line(device: &str, path: &str, label: &str) {
+    let derived = DiskUsage {
+        device: device.to_owned(),
+        path: path.to_owned(),
+        free: 42,
+        label: label.to_owned(),
+        readonly: false,
+        used: 0.25,
+        time: 1_671_095_854,
+    };
+    let point = DataPoint::builder("disk usage,total")
+        .tag("device", device)
+        .tag("path", path)
+        .field("free", 42_i64)
+        .field("label", label)
+        .field("readonly", false)
+        .field("used", 0.25)
+        .timestamp(1_671_095_854)
+        .build()
+        .unwrap();
+
+    assert_eq!(to_line(&derived), to_line(&point));
+}
+
+#[test]
+fn plain_values() {
+    assert_same_line("sda1", "/", "root");
+}
+
+#[test]
+fn tag_delimiters() {
+    assert_same_line("sda 1", "/mnt/a,b=c", "root");
+}
+
+#[test]
+fn string_field_quotes_and_backslashes() {
+    assert_same_line("sda1", "/", r#"C:\Program Files\"quoted""#);
+}
+
+#[test]
+fn string_field_keeps_tag_delimiters() {
+    assert_same_line("sda1", "/", "a,b=c d");
+}
+
+#[test]
+fn escaped_output() {
+    let derived = DiskUsage {
+        device: "sda 1".to_owned(),
+        path: "/mnt/a,b=c".to_owned(),
+        free: 42,
+        label: r#"say "hi"\"#.to_owned(),
+        readonly: true,
+        used: 0.25,
+        time: 1,
+    };
+    assert_eq!(
+        to_line(&derived),
+        r#"disk\ usage\,total,device=sda\ 1,path=/mnt/a\,b\=c free=42i,label="say \"hi\"\\",readonly=t,used=0.25 1
+"#
+    );
+}
+
+#[derive(Clone, Copy)]
+enum Region {
+    UsWest,
+    EuCentral,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Region::UsWest => "us-west",
+            Region::EuCentral => "eu-central",
+        })
+    }
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Healthy,
+}
+
+#[derive(WriteDataPoint)]
+struct Host {
+    #[influxdb(tag, rename = "host name")]
+    name: String,
+    #[influxdb(tag, display)]
+    region: Region,
+    #[influxdb(field, rename = "uptime_s")]
+    uptime: u64,
+}
+
+fn percent(ratio: &f64) -> f64 {
+    ratio * 100.0
+}
+
+#[derive(WriteDataPoint)]
+struct Reading {
+    #[influxdb(measurement)]
+    sensor: String,
+    #[influxdb(flatten)]
+    host: Host,
+    #[influxdb(tag, serde)]
+    status: Status,
+    #[influxdb(tag, serde)]
+    maintenance: Option<Status>,
+    #[influxdb(field, with = "percent")]
+    load: f64,
+    #[influxdb(field)]
+    r#type: String,
+    #[influxdb(timestamp, precision = "ms")]
+    time: DateTime<Utc>,
+}
+
+#[test]
+fn derive_attributes() {
+    let reading = Reading {
+        sensor: "cpu load".to_owned(),
+        host: Host {
+            name: "server01".to_owned(),
+            region: Region::EuCentral,
+            uptime: 3600,
+        },
+        status: Status::Healthy,
+        maintenance: None,
+        load: 0.5,
+        r#type: "total".to_owned(),
+        time: Utc.timestamp_opt(1_671_095_854, 123_456_789).unwrap(),
+    };
+    assert_eq!(
+        to_line(&reading),
+        "cpu\\ load,host\\ name=server01,region=eu-central,status=healthy uptime_s=3600u,load=50,type=\"total\" 1671095854123\n"
+    );
+}
+
+#[test]
+fn derive_without_timestamp() {
+    let host = Host {
+        name: "server02".to_owned(),
+        region: Region::UsWest,
+        uptime: 1,
+    };
+    assert_eq!(
+        to_line(&host),
+        "Host,host\\ name=server02,region=us-west uptime_s=1u\n"
+    );
+}
+
+// Tags shared by several points
+#[derive(WriteDataPoint)]
+struct Location {
+    #[influxdb(tag)]
+    datacenter: String,
+    #[influxdb(tag)]
+    rack: Option<String>,
+}
+
+#[derive(WriteDataPoint)]
+struct Power {
+    #[influxdb(flatten)]
+    location: Location,
+    #[influxdb(field)]
+    watts: f64,
+}
+
+#[test]
+fn flatten_tags_only() {
+    let power = Power {
+        location: Location {
+            datacenter: "dc1".to_owned(),
+            rack: None,
+        },
+        watts: 120.5,
+    };
+    assert_eq!(to_line(&power), "Power,datacenter=dc1 watts=120.5\n");
+}
+
+#[derive(WriteDataPoint)]
+#[measurement = "net"]
+struct Net {
+    #[influxdb(tag)]
+    zone: String,
+    #[influxdb(tag)]
+    interface: String,
+    #[influxdb(flatten)]
+    location: Location,
+    #[influxdb(field)]
+    bytes: i64,
+}
+
+#[test]
+fn tags_are_sorted() {
+    let net = Net {
+        zone: "z".to_owned(),
+        interface: "eth0".to_owned(),
+        location: Location {
+            datacenter: "dc1".to_owned(),
+            rack: Some("r2".to_owned()),
+        },
+        bytes: 10,
+    };
+    let point = DataPoint::builder("net")
+        .tag("zone", "z")
+        .tag("interface", "eth0")
+        .tag("datacenter", "dc1")
+        .tag("rack", "r2")
+        .field("bytes", 10_i64)
+        .build()
+        .unwrap();
+
+    assert_eq!(to_line(&net), to_line(&point));
+    assert_eq!(to_line(&net), "net,datacenter=dc1,interface=eth0,rack=r2,zone=z bytes=10i\n");
+}